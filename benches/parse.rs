@@ -0,0 +1,138 @@
+//! Run with `cargo bench`. Prints the average time per parse for each entry point.
+
+use miniclap::{App, MiniClap, OptionHandler, ParsedAssign, Switch};
+use std::ffi::OsString;
+use std::hint::black_box;
+use std::time::Instant;
+
+#[allow(dead_code)]
+#[derive(MiniClap)]
+struct Opts {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(short, long)]
+    num: Vec<u32>,
+
+    files: Vec<String>,
+}
+
+/// 30 arguments, to measure the cost of setting up a parse rather than of each argument.
+#[allow(dead_code)]
+#[derive(MiniClap)]
+#[miniclap(default_long)]
+struct Wide {
+    flag0: bool,
+    flag1: bool,
+    flag2: bool,
+    flag3: bool,
+    flag4: bool,
+    flag5: bool,
+    flag6: bool,
+    flag7: bool,
+    flag8: bool,
+    flag9: bool,
+    count0: Option<u32>,
+    count1: Option<u32>,
+    count2: Option<u32>,
+    count3: Option<u32>,
+    count4: Option<u32>,
+    count5: Option<u32>,
+    count6: Option<u32>,
+    count7: Option<u32>,
+    count8: Option<u32>,
+    count9: Option<u32>,
+    name0: Option<String>,
+    name1: Option<String>,
+    name2: Option<String>,
+    name3: Option<String>,
+    name4: Option<String>,
+    name5: Option<String>,
+    name6: Option<String>,
+    name7: Option<String>,
+    name8: Option<String>,
+    name9: Option<String>,
+}
+
+const ITERATIONS: u32 = 1_000;
+
+fn argv() -> Vec<OsString> {
+    let mut argv = vec![OsString::from("bench")];
+    for i in 0..1_000 {
+        argv.push("-v".into());
+        argv.push(format!("--num={}", i).into());
+        argv.push(format!("file{}", i).into());
+    }
+    argv
+}
+
+fn bench(name: &str, mut f: impl FnMut(Vec<OsString>)) {
+    let inputs: Vec<_> = (0..ITERATIONS).map(|_| argv()).collect();
+    let start = Instant::now();
+    for input in inputs {
+        f(input);
+    }
+    println!("{:<20} {:>10?}/iter", name, start.elapsed() / ITERATIONS);
+}
+
+/// 500 tokens setting options of an app with 50 of them.
+fn many_options() {
+    let names: Vec<String> = (0..50).map(|i| format!("option{}", i)).collect();
+    let assign = ParsedAssign::new(|x: u32| {
+        black_box(x);
+    });
+    let options: Vec<_> = names
+        .iter()
+        .map(|name| OptionHandler {
+            name,
+            switch: Switch::Long(name),
+            is_raw: false,
+            is_optional_bool: false,
+            default_missing_value: None,
+            require_equals: false,
+            assign: &assign,
+        })
+        .collect();
+    let app = App {
+        options: &options,
+        ..App::default()
+    };
+    let argv: Vec<OsString> = (0..500)
+        .map(|i| format!("--option{}={}", 49 - i % 50, i).into())
+        .collect();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        miniclap::parse_args(&mut argv.iter().cloned(), &app).unwrap();
+    }
+    println!(
+        "{:<20} {:>10?}/iter",
+        "many_options",
+        start.elapsed() / ITERATIONS
+    );
+}
+
+/// A few tokens for an app with 30 arguments, so that building the handlers dominates.
+fn startup() {
+    let argv = ["bench", "--flag3", "--count7=7", "--name9", "x"];
+    let iterations = ITERATIONS * 100;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(Wide::try_parse_from(black_box(argv)).unwrap());
+    }
+    println!(
+        "{:<20} {:>10?}/iter",
+        "startup",
+        start.elapsed() / iterations
+    );
+}
+
+fn main() {
+    bench("try_parse_from", |argv| {
+        black_box(Opts::try_parse_from(argv).unwrap());
+    });
+    bench("try_parse_from_os", |argv| {
+        black_box(Opts::try_parse_from_os(argv.into_iter()).unwrap());
+    });
+    many_options();
+    startup();
+}
@@ -1,5 +1,6 @@
 use miniclap::MiniClap;
 
+#[allow(dead_code)]
 #[derive(Debug, MiniClap)]
 struct Opts {
     #[miniclap(short = "x", long)]
@@ -1,21 +1,170 @@
 extern crate proc_macro;
 
+use proc_macro2::Span;
 use proc_macro2::TokenStream;
 use proc_macro_error::{abort, proc_macro_error};
-use quote::{format_ident, quote};
-use std::collections::BTreeSet;
-use syn::{Field, Ident, Lit, Meta};
+use quote::{format_ident, quote, quote_spanned};
+use std::collections::BTreeMap;
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{Field, Ident, Lit, LitStr, Meta};
 
 #[derive(Debug)]
 enum Attr {
     Short(char),
     Long(String),
     DefaultValue(Lit),
+    OverridesWith(LitStr),
+    Range(LitStr, syn::ExprRange),
+    Subcommand,
+    Flatten,
+    Positional,
+    Trailing(bool),
+    Remaining(bool),
+    TakesValue(bool),
+    Raw(bool),
+    Clearable(bool),
+    Required(bool),
+    Lossy(bool),
+    Count(bool),
+    Max(syn::LitInt),
+    NumberBase(syn::LitInt),
+    ValueDelimiters(LitStr),
+    Exclusive(bool),
+    FlagWithOptionalValue(bool),
+    DefaultMissingValue(LitStr),
+    RequiredIfEq(Ident, LitStr),
+    RequiresAll(Vec<Ident>),
+    DefaultValueIf(Ident, Option<LitStr>, Lit),
+    Group(LitStr),
+    RequireEquals(bool),
+    Deprecated(Option<LitStr>),
+    Transform(LitStr),
+    Prompt(LitStr),
+    ConfigFile(bool),
+}
+
+/// Extracts the field name from a path in an attribute such as `requires_all(a, b)`.
+fn field_ident(path: &syn::Path) -> Ident {
+    match path.get_ident() {
+        Some(ident) => ident.clone(),
+        None => abort!(path, "Expected a field name"),
+    }
+}
+
+/// Collects the nested metas across all `#[miniclap(...)]` attributes.
+fn miniclap_metas(attrs: &[syn::Attribute]) -> Vec<Meta> {
+    attrs
+        .iter()
+        // Only process attributes for this crate.
+        .filter(|a| a.path.is_ident("miniclap"))
+        // Extract nested attributes across all the attributes.
+        .flat_map(|a| match a.parse_meta() {
+            Ok(Meta::List(list)) => list.nested,
+            _ => abort!(a, "Attribute must be a structured list"),
+        })
+        // Ensure that each attribute is a structured format, not a literal.
+        .map(|nm| match nm {
+            syn::NestedMeta::Meta(m) => m,
+            syn::NestedMeta::Lit(l) => abort!(l, "Literals are not valid attributes"),
+        })
+        .collect()
+}
+
+/// Whether `ty` is `Option<bool>`, which is a flag that is `None` unless given.
+fn is_option_bool(ty: &syn::Type) -> bool {
+    let segment = match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => path.segments.last().unwrap(),
+        _ => return false,
+    };
+    match (segment.ident.to_string().as_str(), &segment.arguments) {
+        ("Option", syn::PathArguments::AngleBracketed(args)) => matches!(
+            args.args.first(),
+            Some(syn::GenericArgument::Type(syn::Type::Path(inner))) if inner.path.is_ident("bool")
+        ),
+        _ => false,
+    }
+}
+
+/// Whether the values of `ty` are of one of the types `names`, that is, whether `ty` is one of
+/// them or an `Option`, `Vec` or array of one.
+fn is_value_type(ty: &syn::Type, names: &[&str]) -> bool {
+    let segment = match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => path.segments.last().unwrap(),
+        syn::Type::Array(array) => return is_value_type(&array.elem, names),
+        _ => return false,
+    };
+    match (segment.ident.to_string().as_str(), &segment.arguments) {
+        ("Option", syn::PathArguments::AngleBracketed(args))
+        | ("Vec", syn::PathArguments::AngleBracketed(args)) => match args.args.first() {
+            Some(syn::GenericArgument::Type(inner)) => is_value_type(inner, names),
+            _ => false,
+        },
+        (name, _) => names.contains(&name),
+    }
+}
+
+/// The key and value types of a `Vec<(K, V)>`, whose values are given as `key=value`.
+fn pair_types(ty: &syn::Type) -> Option<(syn::Type, syn::Type)> {
+    let segment = match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => path.segments.last().unwrap(),
+        _ => return None,
+    };
+    let args = match (segment.ident.to_string().as_str(), &segment.arguments) {
+        ("Vec", syn::PathArguments::AngleBracketed(args)) => args,
+        _ => return None,
+    };
+    match args.args.first() {
+        Some(syn::GenericArgument::Type(syn::Type::Tuple(tuple))) if tuple.elems.len() == 2 => {
+            Some((tuple.elems[0].clone(), tuple.elems[1].clone()))
+        }
+        _ => None,
+    }
+}
+
+const INTEGER_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+/// Writes a type as it would be in source, such as `Option<u32>`, for `ArgInfo::type_name`.
+fn type_name(ty: &syn::Type) -> String {
+    quote!(#ty)
+        .to_string()
+        .replace(' ', "")
+        .replace(',', ", ")
+        .replace(';', "; ")
+}
+
+/// Writes a literal as its value would be given on the command line.
+fn lit_string(lit: &Lit) -> String {
+    match lit {
+        Lit::Str(s) => s.value(),
+        Lit::Char(c) => c.value().to_string(),
+        _ => quote!(#lit).to_string(),
+    }
+}
+
+fn is_duration(ty: &syn::Type) -> bool {
+    is_value_type(ty, &["Duration"])
+}
+
+/// Parses a switch-like attribute, which may be written as `name` or `name = true/false`.
+fn bool_attribute(attribute: &Meta) -> bool {
+    match attribute {
+        Meta::Path(_) => true,
+        Meta::NameValue(syn::MetaNameValue {
+            lit: Lit::Bool(b), ..
+        }) => b.value,
+        _ => abort!(
+            attribute,
+            "Attribute must be used as `name` or `name = bool`"
+        ),
+    }
 }
 
 impl Attr {
     fn from_field_attribute(field: &Field, attribute: &Meta) -> Attr {
-        let field_name = field.ident.as_ref().unwrap().to_string();
+        let field_name = field.ident.as_ref().unwrap().unraw().to_string();
         let attr_name = match attribute.path().get_ident() {
             Some(id) => id.to_string(),
             None => abort!(attribute.path(), "Invalid attribute name"),
@@ -48,53 +197,465 @@ impl Attr {
                 Meta::NameValue(mnv) => mnv.lit.clone(),
                 _ => abort!(attribute, "Attribute must be used as `default_value = ...`"),
             }),
+            "overrides_with" => Attr::OverridesWith(match attribute {
+                Meta::NameValue(mnv) => match mnv.lit {
+                    Lit::Str(ref lit_str) => lit_str.clone(),
+                    _ => abort!(mnv.lit, "Only string allowed for `overrides_with`"),
+                },
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `overrides_with = \"...\"`"
+                ),
+            }),
+            "range" => match attribute {
+                Meta::NameValue(mnv) => match mnv.lit {
+                    Lit::Str(ref lit_str) => match lit_str.parse() {
+                        Ok(range) => Attr::Range(lit_str.clone(), range),
+                        Err(_) => abort!(lit_str, "Expected a range such as \"1..=100\""),
+                    },
+                    _ => abort!(mnv.lit, "Only string allowed for `range`"),
+                },
+                _ => abort!(attribute, "Attribute must be used as `range = \"...\"`"),
+            },
+            "subcommand" => match attribute {
+                Meta::Path(_) => Attr::Subcommand,
+                _ => abort!(attribute, "Attribute must be used as `subcommand`"),
+            },
+            "flatten" => match attribute {
+                Meta::Path(_) => Attr::Flatten,
+                _ => abort!(attribute, "Attribute must be used as `flatten`"),
+            },
+            "raw" => Attr::Raw(bool_attribute(attribute)),
+            "trailing" => Attr::Trailing(bool_attribute(attribute)),
+            "remaining" => Attr::Remaining(bool_attribute(attribute)),
+            "takes_value" => Attr::TakesValue(bool_attribute(attribute)),
+            "clearable" => Attr::Clearable(bool_attribute(attribute)),
+            "required" => Attr::Required(bool_attribute(attribute)),
+            "lossy" => Attr::Lossy(bool_attribute(attribute)),
+            "count" => Attr::Count(bool_attribute(attribute)),
+            "max" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Int(lit_int),
+                    ..
+                }) => Attr::Max(lit_int.clone()),
+                _ => abort!(attribute, "Attribute must be used as `max = <integer>`"),
+            },
+            "number_base" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Int(lit_int),
+                    ..
+                }) => match lit_int.base10_parse::<u32>() {
+                    Ok(2..=36) => Attr::NumberBase(lit_int.clone()),
+                    _ => abort!(lit_int, "The base must be between 2 and 36"),
+                },
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `number_base = <integer>`"
+                ),
+            },
+            "value_delimiters" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) if !lit_str.value().is_empty() => Attr::ValueDelimiters(lit_str.clone()),
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `value_delimiters = \"...\"` with at least one \
+                    character"
+                ),
+            },
+            "exclusive" => Attr::Exclusive(bool_attribute(attribute)),
+            "require_equals" => Attr::RequireEquals(bool_attribute(attribute)),
+            "config_file" => Attr::ConfigFile(bool_attribute(attribute)),
+            "prompt" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => Attr::Prompt(lit_str.clone()),
+                _ => abort!(attribute, "Attribute must be used as `prompt = \"...\"`"),
+            },
+            "transform" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => match lit_str.value().as_str() {
+                    "trim" | "uppercase" | "lowercase" => Attr::Transform(lit_str.clone()),
+                    _ if lit_str.parse::<syn::Path>().is_ok() => Attr::Transform(lit_str.clone()),
+                    _ => abort!(
+                        lit_str,
+                        "Expected `trim`, `uppercase`, `lowercase` or a function path"
+                    ),
+                },
+                _ => abort!(attribute, "Attribute must be used as `transform = \"...\"`"),
+            },
+            "deprecated" => match attribute {
+                Meta::Path(_) => Attr::Deprecated(None),
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => Attr::Deprecated(Some(lit_str.clone())),
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `deprecated` or `deprecated = \"...\"`"
+                ),
+            },
+            "flag_with_optional_value" => Attr::FlagWithOptionalValue(bool_attribute(attribute)),
+            "default_missing_value" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => Attr::DefaultMissingValue(lit_str.clone()),
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `default_missing_value = \"...\"`"
+                ),
+            },
+            "required_if_eq" => match attribute {
+                Meta::List(list) if list.nested.len() == 1 => match list.nested.first() {
+                    Some(syn::NestedMeta::Meta(Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        lit: Lit::Str(value),
+                        ..
+                    }))) => Attr::RequiredIfEq(field_ident(path), value.clone()),
+                    _ => abort!(list, "Expected `required_if_eq(field = \"value\")`"),
+                },
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `required_if_eq(field = \"value\")`"
+                ),
+            },
+            "group" => match attribute {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => Attr::Group(lit_str.clone()),
+                _ => abort!(attribute, "Attribute must be used as `group = \"...\"`"),
+            },
+            "default_value_if" => match attribute {
+                Meta::List(list) if list.nested.len() == 2 => {
+                    let mut nested = list.nested.iter();
+                    let (other, value) = match nested.next() {
+                        Some(syn::NestedMeta::Meta(Meta::Path(path))) => (field_ident(path), None),
+                        Some(syn::NestedMeta::Meta(Meta::NameValue(syn::MetaNameValue {
+                            path,
+                            lit: Lit::Str(value),
+                            ..
+                        }))) => (field_ident(path), Some(value.clone())),
+                        _ => abort!(list, "Expected a field name or `field = \"value\"`"),
+                    };
+                    match nested.next() {
+                        Some(syn::NestedMeta::Lit(lit)) => {
+                            Attr::DefaultValueIf(other, value, lit.clone())
+                        }
+                        _ => abort!(list, "Expected a default value"),
+                    }
+                }
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `default_value_if(field, ...)` or \
+                    `default_value_if(field = \"value\", ...)`"
+                ),
+            },
+            "requires_all" => match attribute {
+                Meta::List(list) => Attr::RequiresAll(
+                    list.nested
+                        .iter()
+                        .map(|nested| match nested {
+                            syn::NestedMeta::Meta(Meta::Path(path)) => field_ident(path),
+                            _ => abort!(nested, "Expected a field name"),
+                        })
+                        .collect(),
+                ),
+                _ => abort!(
+                    attribute,
+                    "Attribute must be used as `requires_all(a, b, ...)`"
+                ),
+            },
+            "positional" => match attribute {
+                Meta::Path(_) => Attr::Positional,
+                _ => abort!(attribute, "Attribute must be used as `positional`"),
+            },
             _ => abort!(attribute.path(), "Unknown attribute"),
         }
     }
 
     fn all_from_field(field: &Field) -> Vec<(Meta, Attr)> {
-        field
-            .attrs
-            .iter()
-            // Only process attributes for this crate.
-            .filter(|a| a.path.is_ident("miniclap"))
-            // Extract nested attributes across all the attributes.
-            .flat_map(|a| match a.parse_meta() {
-                Ok(Meta::List(list)) => list.nested,
-                _ => abort!(a, "Attribute must be a structured list"),
-            })
-            // Ensure that each attribute is a structured format, not a literal.
-            .map(|nm| match nm {
-                syn::NestedMeta::Meta(m) => m,
-                syn::NestedMeta::Lit(l) => abort!(l, "Literals are not valid attributes"),
-            })
-            // Parse the attribute
+        miniclap_metas(&field.attrs)
+            .into_iter()
             .map(|meta| {
-                let attr = Attr::from_field_attribute(&field, &meta);
+                let attr = Attr::from_field_attribute(field, &meta);
                 (meta, attr)
             })
             .collect()
     }
 }
 
+/// Attributes placed on the struct or enum itself.
+#[derive(Default)]
+struct Settings {
+    /// Fields without `short`, `long` or `positional` get a long switch named after the field.
+    default_long: bool,
+    /// Every `bool` flag with a long switch also gets a `--no-` switch that clears it.
+    auto_negate_bools: bool,
+    /// A `fn(&Self) -> miniclap::Result<()>` run once the struct is built.
+    validate: Option<syn::Path>,
+    /// A `fn(&mut miniclap::AppBuilder)` that adds arguments at runtime, run on each parse.
+    augment: Option<syn::Path>,
+    /// Also implement `Default`, using the `default_value`s.
+    derive_default: bool,
+    /// Also generate `to_args`, which writes the value back out as arguments.
+    to_args: bool,
+    /// Also generate `matches_from` and a public `{Name}Matches` with getters, to look at or
+    /// change the arguments before the struct is built. A flattened struct needs it too for the
+    /// getters of its own arguments.
+    matches: bool,
+    /// Arguments starting with an unknown switch are taken as positionals.
+    unknown_as_positional: bool,
+    /// Arguments such as `-5` or `-1.5` are taken as positionals unless `-5` or `-1` is a switch.
+    allow_negative_numbers: bool,
+    /// An option may be given as `--name = value`, in three arguments.
+    allow_space_around_equals: bool,
+    /// Positional arguments left over once all the positional fields are filled are dropped with
+    /// a warning.
+    ignore_extra_positionals: bool,
+    /// A short switch that is both a flag and an option, which only a flattened struct can cause,
+    /// is taken as the option instead of the flag.
+    prefer_short_options: bool,
+    /// Every argument with a long switch falls back to the environment variable named after the
+    /// field in upper case with this prefix, as in `PREFIX_FIELD`.
+    env_prefix: Option<LitStr>,
+    /// Parsing fails once more than this many arguments are given.
+    max_args: Option<syn::LitInt>,
+    /// Short switches that no field may use, such as ones kept for help and version.
+    reserved_shorts: Option<LitStr>,
+    /// The constraints on each named group of fields declared with `group(name, ...)`.
+    groups: Vec<GroupSettings>,
+}
+
+/// How many of a group's fields may be given. By default, at most one may be.
+struct GroupSettings {
+    name: Ident,
+    /// At least one field must be given.
+    required: bool,
+    /// More than one field may be given.
+    multiple: bool,
+}
+
+impl Settings {
+    fn from_attributes(attrs: &[syn::Attribute]) -> Settings {
+        let mut settings = Settings::default();
+        for meta in miniclap_metas(attrs) {
+            let attr_name = match meta.path().get_ident() {
+                Some(id) => id.to_string(),
+                None => abort!(meta.path(), "Invalid attribute name"),
+            };
+            match attr_name.as_str() {
+                "default_long" => settings.default_long = bool_attribute(&meta),
+                "auto_negate_bools" => settings.auto_negate_bools = bool_attribute(&meta),
+                "derive_default" => settings.derive_default = bool_attribute(&meta),
+                "to_args" => settings.to_args = bool_attribute(&meta),
+                "matches" => settings.matches = bool_attribute(&meta),
+                "unknown_as_positional" => settings.unknown_as_positional = bool_attribute(&meta),
+                "allow_negative_numbers" => settings.allow_negative_numbers = bool_attribute(&meta),
+                "allow_space_around_equals" => {
+                    settings.allow_space_around_equals = bool_attribute(&meta)
+                }
+                "ignore_extra_positionals" => {
+                    settings.ignore_extra_positionals = bool_attribute(&meta)
+                }
+                "prefer_short_options" => settings.prefer_short_options = bool_attribute(&meta),
+                "max_args" => match &meta {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Int(lit_int),
+                        ..
+                    }) => settings.max_args = Some(lit_int.clone()),
+                    _ => abort!(meta, "Attribute must be used as `max_args = <integer>`"),
+                },
+                "env_prefix" => match &meta {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => settings.env_prefix = Some(lit_str.clone()),
+                    _ => abort!(meta, "Attribute must be used as `env_prefix = \"...\"`"),
+                },
+                "reserved_shorts" => match &meta {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => settings.reserved_shorts = Some(lit_str.clone()),
+                    _ => abort!(
+                        meta,
+                        "Attribute must be used as `reserved_shorts = \"...\"`"
+                    ),
+                },
+                "group" => match &meta {
+                    Meta::List(list) => {
+                        let mut nested = list.nested.iter().map(|nested| match nested {
+                            syn::NestedMeta::Meta(Meta::Path(path)) => field_ident(path),
+                            _ => abort!(nested, "Expected a group name, `required` or `multiple`"),
+                        });
+                        let mut group = match nested.next() {
+                            Some(name) => GroupSettings {
+                                name,
+                                required: false,
+                                multiple: false,
+                            },
+                            None => abort!(list, "Expected a group name"),
+                        };
+                        for setting in nested {
+                            match setting.to_string().as_str() {
+                                "required" => group.required = true,
+                                "multiple" => group.multiple = true,
+                                _ => abort!(setting, "Expected `required` or `multiple`"),
+                            }
+                        }
+                        if settings.groups.iter().any(|other| other.name == group.name) {
+                            abort!(group.name, "May only specify once");
+                        }
+                        settings.groups.push(group);
+                    }
+                    _ => abort!(
+                        meta,
+                        "Attribute must be used as `group(name)`, optionally followed by \
+                        `required` and `multiple`"
+                    ),
+                },
+                "validate" => match &meta {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => match lit_str.parse() {
+                        Ok(path) => settings.validate = Some(path),
+                        Err(_) => abort!(lit_str, "Expected a function path"),
+                    },
+                    _ => abort!(meta, "Attribute must be used as `validate = \"path\"`"),
+                },
+                "augment" => match &meta {
+                    Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => match lit_str.parse() {
+                        Ok(path) => settings.augment = Some(path),
+                        Err(_) => abort!(lit_str, "Expected a function path"),
+                    },
+                    _ => abort!(meta, "Attribute must be used as `augment = \"path\"`"),
+                },
+                _ => abort!(meta.path(), "Unknown attribute"),
+            }
+        }
+        settings
+    }
+}
+
+#[derive(Default)]
 struct App {
     by_position: Vec<Arg>,
     by_switch: Vec<Arg>,
+    subcommand: Option<Box<Arg>>,
+    flattened: Vec<Flattened>,
+    overrides: Vec<(Ident, Ident)>,
+    requirements: Vec<Requirement>,
+    conditional_defaults: Vec<ConditionalDefault>,
+    groups: Vec<Group>,
+    validate: Option<syn::Path>,
+    augment: Option<syn::Path>,
+    derive_default: bool,
+    to_args: bool,
+    matches: bool,
+    unknown_as_positional: bool,
+    allow_negative_numbers: bool,
+    allow_space_around_equals: bool,
+    ignore_extra_positionals: bool,
+    prefer_short_options: bool,
+    max_args: Option<syn::LitInt>,
+    /// The long switch of the option naming a config file, if there is one.
+    config_file: Option<String>,
+}
+
+/// A condition under which other arguments must also be given.
+enum Requirement {
+    /// `field` must be given if `other` has the value `value`.
+    IfEq {
+        field: Ident,
+        other: Ident,
+        value: LitStr,
+    },
+    /// If `field` is given, all of `others` must be given too.
+    All { field: Ident, others: Vec<Ident> },
+}
+
+/// Fields of which at most one may be given, unless `multiple`, and at least one must be given if
+/// `required`.
+struct Group {
+    name: String,
+    members: Vec<Ident>,
+    required: bool,
+    multiple: bool,
+}
+
+/// A default for `field` that applies only if `other` is given, with the value `value` if there
+/// is one.
+struct ConditionalDefault {
+    field: Ident,
+    other: Ident,
+    value: Option<LitStr>,
+    default: Lit,
 }
 
 impl App {
-    fn from_named_fields(fields: &syn::FieldsNamed) -> App {
+    fn from_named_fields(fields: &syn::FieldsNamed, settings: &Settings) -> App {
         let mut by_position: Vec<Arg> = Vec::new();
         let mut by_switch: Vec<Arg> = Vec::new();
-        let mut short_switches = BTreeSet::new();
-        let mut long_switches = BTreeSet::new();
+        // Each switch maps to the field that uses it and where it was declared.
+        let mut short_switches: BTreeMap<char, (Ident, Span)> = BTreeMap::new();
+        let mut long_switches: BTreeMap<String, (Ident, Span)> = BTreeMap::new();
+        let mut overrides_with = Vec::new();
+        let mut subcommand = None;
+        let mut flattened = Vec::new();
+        let mut requirements = Vec::new();
+        let mut conditional_defaults = Vec::new();
+        let mut groups: Vec<Group> = Vec::new();
         for f in &fields.named {
             let ident = f.ident.clone().unwrap();
             let attrs = Attr::all_from_field(f);
 
+            if attrs.iter().any(|(_, a)| matches!(a, Attr::Flatten)) {
+                if attrs.len() > 1 {
+                    abort!(f, "A flattened field cannot have other attributes");
+                }
+                flattened.push(Flattened {
+                    name: ident,
+                    ty: f.ty.clone(),
+                });
+                continue;
+            }
+
             let mut short = None;
             let mut long = None;
             let mut default_value = None;
+            let mut extra_default_values = Vec::new();
+            let mut range = None;
+            let mut is_subcommand = false;
+            let mut is_positional = false;
+            let mut is_trailing = false;
+            let mut is_remaining = false;
+            let mut takes_value = None;
+            let mut is_raw = false;
+            let mut is_clearable = false;
+            let mut is_required_vec = false;
+            let mut is_lossy = false;
+            let mut is_count = false;
+            let mut count_max = None;
+            let mut number_base = None;
+            let mut is_exclusive = false;
+            let mut require_equals = false;
+            let mut deprecated = None;
+            let mut transform = None;
+            let mut prompt = None;
+            let mut is_config_file = false;
+            let mut value_delimiters = None;
+            let mut is_optional_bool = false;
+            let mut default_missing_value = None;
 
             for (m, a) in attrs {
                 match a {
@@ -102,27 +663,180 @@ impl App {
                         if short.replace(c).is_some() {
                             abort!(m, "May only specify once");
                         }
-                        if !short_switches.insert(c) {
-                            abort!(m, "Short already used");
+                        if let Some(reserved) = &settings.reserved_shorts {
+                            if reserved.value().contains(c) {
+                                abort!(m, "Short `-{}` is reserved", c;
+                                    note = reserved.span() => "`-{}` reserved here", c);
+                            }
+                        }
+                        if let Some((other, span)) =
+                            short_switches.insert(c, (ident.clone(), m.span()))
+                        {
+                            abort!(m, "Short `-{}` already used by `{}`", c, other;
+                                note = span => "`-{}` first used here", c);
                         }
                     }
                     Attr::Long(name) => {
                         if long.replace(name.clone()).is_some() {
                             abort!(m, "May only specify once");
                         }
-                        if !long_switches.insert(name) {
-                            abort!(m, "Long already used");
+                        if let Some((other, span)) =
+                            long_switches.insert(name.clone(), (ident.clone(), m.span()))
+                        {
+                            abort!(m, "Long `--{}` already used by `{}`", name, other;
+                                note = span => "`--{}` first used here", name);
+                        }
+                    }
+                    Attr::DefaultValue(lit) => match default_value {
+                        // Only a `Vec` may have several, which is checked once the type is known.
+                        Some(_) => extra_default_values.push(lit),
+                        None => default_value = Some(lit),
+                    },
+                    Attr::OverridesWith(other) => overrides_with.push((ident.clone(), other)),
+                    Attr::Range(lit_str, expr) => {
+                        if range.replace((lit_str, expr)).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::Subcommand => {
+                        if subcommand.is_some() {
+                            abort!(m, "Only one field may be a subcommand");
+                        }
+                        is_subcommand = true;
+                    }
+                    Attr::Flatten => unreachable!(),
+                    Attr::Positional => is_positional = true,
+                    Attr::Trailing(value) => is_trailing = value,
+                    Attr::Remaining(value) => is_remaining = value,
+                    Attr::TakesValue(value) => {
+                        if takes_value.replace(value).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::Raw(value) => is_raw = value,
+                    Attr::Clearable(value) => is_clearable = value,
+                    Attr::Required(value) => is_required_vec = value,
+                    Attr::Lossy(value) => is_lossy = value,
+                    Attr::Count(value) => is_count = value,
+                    Attr::Max(lit_int) => {
+                        if count_max.replace(lit_int).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::NumberBase(lit_int) => {
+                        if number_base.replace(lit_int).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::ValueDelimiters(lit_str) => {
+                        if value_delimiters.replace(lit_str).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::Exclusive(value) => is_exclusive = value,
+                    Attr::RequireEquals(value) => require_equals = value,
+                    Attr::ConfigFile(value) => is_config_file = value,
+                    Attr::Prompt(lit_str) => {
+                        if prompt.replace(lit_str).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::Transform(lit_str) => {
+                        if transform.replace(lit_str).is_some() {
+                            abort!(m, "May only specify once");
+                        }
+                    }
+                    Attr::Deprecated(note) => {
+                        if deprecated.replace(note).is_some() {
+                            abort!(m, "May only specify once");
                         }
                     }
-                    Attr::DefaultValue(lit) => {
-                        if default_value.replace(lit).is_some() {
+                    Attr::FlagWithOptionalValue(value) => is_optional_bool = value,
+                    Attr::DefaultMissingValue(lit_str) => {
+                        if default_missing_value.replace(lit_str).is_some() {
                             abort!(m, "May only specify once");
                         }
                     }
+                    Attr::RequiredIfEq(other, value) => requirements.push(Requirement::IfEq {
+                        field: ident.clone(),
+                        other,
+                        value,
+                    }),
+                    Attr::RequiresAll(others) => requirements.push(Requirement::All {
+                        field: ident.clone(),
+                        others,
+                    }),
+                    Attr::Group(lit_str) => {
+                        let name = lit_str.value();
+                        match groups.iter_mut().find(|group| group.name == name) {
+                            Some(group) => group.members.push(ident.clone()),
+                            None => {
+                                let settings =
+                                    settings.groups.iter().find(|group| group.name == name);
+                                groups.push(Group {
+                                    name,
+                                    members: vec![ident.clone()],
+                                    required: matches!(settings, Some(s) if s.required),
+                                    multiple: matches!(settings, Some(s) if s.multiple),
+                                });
+                            }
+                        }
+                    }
+                    Attr::DefaultValueIf(other, value, default) => {
+                        conditional_defaults.push(ConditionalDefault {
+                            field: ident.clone(),
+                            other,
+                            value,
+                            default,
+                        })
+                    }
+                }
+            }
+
+            if is_positional && (short.is_some() || long.is_some() || is_subcommand) {
+                abort!(
+                    f,
+                    "A positional argument cannot have a switch or be a subcommand"
+                );
+            }
+
+            let is_bare = short.is_none() && long.is_none() && !is_subcommand;
+            if is_trailing && !is_bare {
+                abort!(
+                    f,
+                    "A trailing argument cannot have a switch or be a subcommand"
+                );
+            }
+
+            if is_remaining && (!is_bare || is_positional || is_trailing) {
+                abort!(
+                    f,
+                    "A remaining argument cannot have a switch or be a subcommand, positional or \
+                    trailing"
+                );
+            }
+
+            if settings.default_long && is_bare && !is_positional && !is_trailing && !is_remaining {
+                let name = ident.to_string();
+                if let Some((other, span)) =
+                    long_switches.insert(name.clone(), (ident.clone(), ident.span()))
+                {
+                    abort!(ident, "Long `--{}` already used by `{}`", name, other;
+                        note = span => "`--{}` first used here", name);
                 }
+                long = Some(name);
             }
 
-            let index = if short.is_none() && long.is_none() {
+            if is_subcommand && (short.is_some() || long.is_some()) {
+                abort!(f, "A subcommand cannot have a `short` or `long` switch");
+            }
+
+            let index = if short.is_none()
+                && long.is_none()
+                && !is_subcommand
+                && !is_trailing
+                && !is_remaining
+            {
                 Some(by_position.len())
             } else {
                 None
@@ -130,6 +844,8 @@ impl App {
             let mut is_required = true;
             let mut is_flag = false;
             let mut is_multiple = false;
+            let mut is_tri_state = false;
+            let mut array_len = None;
 
             match f.ty {
                 syn::Type::Path(syn::TypePath {
@@ -138,40 +854,340 @@ impl App {
                 }) => match segments.last().unwrap().ident.to_string().as_str() {
                     "Option" => {
                         is_required = false;
+                        is_tri_state = index.is_none() && is_option_bool(&f.ty);
+                        is_flag = is_tri_state;
                     }
                     "Vec" => {
                         is_multiple = true;
                         is_required = false;
                     }
-                    "bool" => {
-                        if index.is_none() {
-                            is_required = false;
-                            is_flag = true;
-                        }
+                    "bool" if index.is_none() => {
+                        is_required = false;
+                        is_flag = true;
                     }
                     _ => (),
                 },
-                _ => todo!(),
+                syn::Type::Array(syn::TypeArray {
+                    ref elem, ref len, ..
+                }) => {
+                    if index.is_none() {
+                        abort!(f.ty, "An array may only be a positional argument");
+                    }
+                    match len {
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: Lit::Int(lit_int),
+                            ..
+                        }) if lit_int.base10_parse::<usize>().is_ok() => {
+                            array_len = Some((lit_int.clone(), elem.as_ref().clone()));
+                        }
+                        _ => abort!(len, "The length of an array must be an integer literal"),
+                    }
+                }
+                _ => abort!(f.ty, "Unsupported argument type"),
+            }
+
+            if array_len.is_some() && (default_value.is_some() || range.is_some() || is_lossy) {
+                abort!(
+                    f,
+                    "An array cannot have a default value or a range, or be `lossy`"
+                );
+            }
+
+            if is_tri_state {
+                if let Some(lit) = &default_value {
+                    abort!(lit, "An `Option<bool>` flag cannot have a default value");
+                }
+                if is_optional_bool || is_exclusive || is_count {
+                    abort!(
+                        f,
+                        "An `Option<bool>` flag cannot be used with `flag_with_optional_value`, \
+                        `exclusive` or `count`"
+                    );
+                }
+            }
+
+            // A type that would take a value is set from whether the switch is given instead.
+            let mut is_coerced_flag = false;
+            if takes_value == Some(false) && !is_flag {
+                if is_multiple || !is_required || index.is_some() || is_subcommand || is_count {
+                    abort!(
+                        f,
+                        "`takes_value = false` may only be used on a field with a switch that is \
+                        not an `Option`, a `Vec` or a `count`"
+                    );
+                }
+                if let Some(lit) = &default_value {
+                    abort!(lit, "A flag cannot have a default value");
+                }
+                is_required = false;
+                is_flag = true;
+                is_coerced_flag = true;
+            } else if takes_value == Some(true) && is_flag {
+                abort!(
+                    f,
+                    "A `bool` flag cannot take a value; use `flag_with_optional_value`"
+                );
+            }
+
+            if is_count {
+                if is_flag || is_multiple || !is_required || index.is_some() {
+                    abort!(
+                        f,
+                        "`count` may only be used on an integer or `CountLevel` with a switch"
+                    );
+                }
+                if is_optional_bool {
+                    abort!(f, "`count` cannot be used with `flag_with_optional_value`");
+                }
+                // Given like a flag, but counts the occurrences.
+                is_required = false;
+                is_flag = true;
+            } else if let Some(lit_int) = &count_max {
+                abort!(lit_int, "`max` may only be used with `count`");
+            }
+
+            let pair = pair_types(&f.ty);
+            if pair.is_some() {
+                if let Some(lit) = &default_value {
+                    abort!(lit, "A `Vec<(K, V)>` cannot have a default value");
+                }
+                if range.is_some() || is_clearable || number_base.is_some() {
+                    abort!(
+                        f,
+                        "A `Vec<(K, V)>` cannot have a range, be `clearable` or be in a \
+                        `number_base`"
+                    );
+                }
+            }
+
+            if let Some(lit_int) = &number_base {
+                if is_flag || is_subcommand || is_clearable || is_duration(&f.ty) {
+                    abort!(
+                        lit_int,
+                        "`number_base` may only be used on an integer argument"
+                    );
+                }
+            }
+
+            if is_config_file && (long.is_none() || is_flag || is_multiple || is_subcommand) {
+                abort!(
+                    f,
+                    "`config_file` may only be used on an option with a long switch that takes \
+                    one value"
+                );
+            }
+
+            if let Some(lit_str) = &prompt {
+                if is_flag
+                    || is_multiple
+                    || index.is_some()
+                    || is_subcommand
+                    || array_len.is_some()
+                    || default_value.is_some()
+                {
+                    abort!(
+                        lit_str,
+                        "`prompt` may only be used on an option with a switch that takes one \
+                        value and has no default value"
+                    );
+                }
+            }
+
+            if let Some(lit_str) = &transform {
+                if is_flag || is_subcommand || is_clearable || number_base.is_some() {
+                    abort!(
+                        lit_str,
+                        "`transform` may only be used on an argument with a value that is not \
+                        `clearable` or in a `number_base`"
+                    );
+                }
+            }
+
+            if let Some(lit_str) = &value_delimiters {
+                if !is_multiple || is_raw || is_clearable {
+                    abort!(
+                        lit_str,
+                        "`value_delimiters` may only be used on a `Vec` that is not `raw` or \
+                        `clearable`"
+                    );
+                }
+            }
+
+            if is_exclusive && (!is_flag || is_count) {
+                abort!(f, "`exclusive` may only be used on a `bool` flag");
+            }
+
+            if (is_flag || is_subcommand) && conditional_defaults.iter().any(|d| d.field == ident) {
+                abort!(
+                    f,
+                    "`default_value_if` cannot be used on a flag or subcommand"
+                );
+            }
+
+            // A `bool` flag that takes an optional value becomes an option below.
+            let is_plain_flag = is_flag && !is_optional_bool && default_value.is_none();
+            if require_equals && (is_plain_flag || index.is_some() || is_subcommand || is_raw) {
+                abort!(
+                    f,
+                    "`require_equals` may only be used on an option that is not `raw`"
+                );
+            }
+
+            if is_trailing && !is_multiple {
+                abort!(f, "`trailing` may only be used on a `Vec`");
+            }
+
+            if is_remaining && !is_multiple {
+                abort!(f, "`remaining` may only be used on a `Vec`");
+            }
+
+            if is_raw && (index.is_some() || !is_multiple) {
+                abort!(f, "`raw` may only be used on a `Vec` option with a switch");
+            }
+
+            if is_lossy && index.is_none() {
+                abort!(f, "`lossy` may only be used on a positional argument");
+            }
+
+            if let (false, Some(lit)) = (is_multiple, extra_default_values.first()) {
+                abort!(lit, "Only a `Vec` may have more than one default value");
+            }
+
+            if is_required_vec {
+                if !is_multiple {
+                    abort!(f, "`required` may only be used on a `Vec`");
+                }
+                if let Some(lit) = &default_value {
+                    abort!(lit, "A required argument cannot have a default value");
+                }
+                // At least one value must be given.
+                is_required = true;
+            }
+
+            if is_clearable && (index.is_some() || !is_multiple || is_raw) {
+                abort!(
+                    f,
+                    "`clearable` may only be used on a `Vec` option with a switch"
+                );
+            }
+
+            // A flag with a default can be set either way, so it also takes an optional value.
+            if is_flag && !is_count {
+                match &default_value {
+                    Some(Lit::Bool(_)) => is_optional_bool = true,
+                    Some(lit) => {
+                        abort!(lit, "The default value of a flag must be `true` or `false`")
+                    }
+                    None => (),
+                }
+            }
+
+            if is_optional_bool {
+                if !is_flag {
+                    abort!(
+                        f,
+                        "`flag_with_optional_value` may only be used on a `bool` flag"
+                    );
+                }
+                // Parsed as an option with a default rather than as a flag.
+                is_flag = false;
+                default_value.get_or_insert_with(|| syn::parse_quote!(false));
+            }
+
+            if let (true, Some(lit_str)) = (is_flag || index.is_some(), &default_missing_value) {
+                abort!(
+                    lit_str,
+                    "`default_missing_value` may only be used on an option"
+                );
+            }
+
+            if let (true, Some((lit_str, _))) = (is_flag, &range) {
+                abort!(lit_str, "`range` cannot be used on a flag");
             }
 
+            // An `Option<bool>` flag can always be negated, since `None` is left for not given.
+            let is_negatable = is_tri_state || (is_flag && !is_count && settings.auto_negate_bools);
+            let negated_long = match (&long, is_negatable) {
+                (Some(long), true) => {
+                    let name = format!("no-{}", long);
+                    if let Some((other, span)) =
+                        long_switches.insert(name.clone(), (ident.clone(), ident.span()))
+                    {
+                        abort!(ident, "Long `--{}` already used by `{}`", name, other;
+                            note = span => "`--{}` first used here", name);
+                    }
+                    Some(name)
+                }
+                _ => None,
+            };
+
+            let env = match (&settings.env_prefix, &long) {
+                (Some(prefix), Some(_)) if !is_raw && !is_count => Some(format!(
+                    "{}_{}",
+                    prefix.value(),
+                    ident.unraw().to_string().to_uppercase()
+                )),
+                _ => None,
+            };
+
             let arg = Arg {
                 name: ident,
+                ty: f.ty.clone(),
                 index,
                 short,
                 long,
+                negated_long,
                 default_value,
+                extra_default_values,
+                range,
                 is_flag,
                 is_required,
                 is_multiple,
+                is_subcommand,
+                is_raw,
+                is_clearable,
+                is_lossy,
+                is_duration: is_duration(&f.ty),
+                is_os_string: is_value_type(&f.ty, &["PathBuf", "OsString"]),
+                pair,
+                env,
+                is_count,
+                is_count_level: is_count && !is_value_type(&f.ty, INTEGER_TYPES),
+                count_max,
+                number_base,
+                is_exclusive,
+                value_delimiters,
+                is_trailing,
+                is_remaining,
+                is_coerced_flag,
+                is_tri_state,
+                array_len,
+                require_equals,
+                deprecated,
+                transform,
+                prompt,
+                is_config_file,
+                config_key: None,
+                is_optional_bool,
+                default_missing_value,
             };
 
-            if index.is_some() {
+            if is_subcommand {
+                subcommand = Some(Box::new(arg));
+            } else if index.is_some() {
                 if let Some(prev) = by_position.last() {
                     if is_required && !prev.is_required {
+                        // Positional arguments are filled in order, so the value meant for this
+                        // one would always go to the optional one before it.
                         abort!(
                             f.ty,
-                            "Required positional argument may not follow optional/multiple \
-                            positional argument"
+                            "Required positional argument `{}` may not follow optional/multiple \
+                            positional argument `{}`", arg.name, prev.name;
+                            note = prev.name.span() => "values fill positional arguments in \
+                                order, so `{}` would take the value meant for `{}`",
+                                prev.name, arg.name;
+                            help = "make `{}` required, make `{}` an `Option`, or give \
+                                one of them a switch", prev.name, arg.name
                         );
                     } else if prev.is_multiple {
                         abort!(
@@ -184,40 +1200,362 @@ impl App {
 
                 by_position.push(arg);
             } else {
+                if arg.is_trailing && by_switch.iter().any(|other| other.is_trailing) {
+                    abort!(f, "Only one field may be trailing");
+                }
+                if arg.is_remaining && by_switch.iter().any(|other| other.is_remaining) {
+                    abort!(f, "Only one field may be remaining");
+                }
+                if arg.is_config_file && by_switch.iter().any(|other| other.is_config_file) {
+                    abort!(f, "Only one field may be a config file");
+                }
                 by_switch.push(arg);
             }
         }
+
+        // Positional arguments are all taken before the subcommand name, so one that may be left
+        // out, or that takes any number of values, would take the subcommand name instead.
+        if subcommand.is_some() {
+            let optional = by_position
+                .iter()
+                .find(|arg| !arg.is_required || arg.is_multiple || arg.default_value.is_some());
+            if let Some(arg) = optional {
+                abort!(
+                    arg.name,
+                    "A positional argument before a subcommand must be required and take one value"
+                );
+            }
+        }
+
+        // Left over positional arguments go to a subcommand or a `Vec` positional first.
+        if let Some(arg) = by_switch.iter().find(|arg| arg.is_remaining) {
+            if subcommand.is_some() || by_position.iter().any(|other| other.is_multiple) {
+                abort!(
+                    arg.name,
+                    "A remaining argument cannot be used with a subcommand or a `Vec` positional \
+                    argument"
+                );
+            }
+        }
+
+        // Resolve the overridden field names now that all fields are known.
+        let overrides = overrides_with
+            .into_iter()
+            .map(|(ident, other)| {
+                match fields
+                    .named
+                    .iter()
+                    .find_map(|f| f.ident.as_ref().filter(|id| **id == other.value()))
+                {
+                    Some(id) if flattened.iter().any(|f| f.name == *id) => {
+                        abort!(other, "Cannot refer to a flattened field")
+                    }
+                    Some(id) if *id == ident => abort!(other, "Field cannot override itself"),
+                    Some(id) => (ident, id.clone()),
+                    None => abort!(other, "No field with this name"),
+                }
+            })
+            .collect();
+
+        let conditions = conditional_defaults
+            .iter()
+            .map(|d| std::slice::from_ref(&d.other));
+        for others in requirements
+            .iter()
+            .map(|requirement| match requirement {
+                Requirement::IfEq { other, .. } => std::slice::from_ref(other),
+                Requirement::All { others, .. } => others.as_slice(),
+            })
+            .chain(conditions)
+        {
+            for other in others {
+                if !fields.named.iter().any(|f| f.ident.as_ref() == Some(other)) {
+                    abort!(other, "No field with this name");
+                }
+                if flattened.iter().any(|f| f.name == *other) {
+                    abort!(other, "Cannot refer to a flattened field");
+                }
+            }
+        }
+
+        // With a config file, every other argument with a long switch falls back to the value
+        // under its long name.
+        let config_file = by_switch
+            .iter()
+            .find(|arg| arg.is_config_file)
+            .and_then(|arg| arg.long.clone());
+        if config_file.is_some() {
+            for arg in &mut by_switch {
+                if !arg.is_config_file && !arg.is_count && !arg.is_raw && !arg.is_trailing {
+                    arg.config_key = arg.long.clone();
+                }
+            }
+        }
+
         App {
             by_position,
             by_switch,
+            subcommand,
+            flattened,
+            overrides,
+            requirements,
+            conditional_defaults,
+            groups,
+            validate: settings.validate.clone(),
+            augment: settings.augment.clone(),
+            derive_default: settings.derive_default,
+            to_args: settings.to_args,
+            matches: settings.matches,
+            unknown_as_positional: settings.unknown_as_positional,
+            allow_negative_numbers: settings.allow_negative_numbers,
+            allow_space_around_equals: settings.allow_space_around_equals,
+            ignore_extra_positionals: settings.ignore_extra_positionals,
+            prefer_short_options: settings.prefer_short_options,
+            max_args: settings.max_args.clone(),
+            config_file,
         }
     }
+}
+
+enum Input {
+    Struct(Box<App>),
+    /// The subcommands, and whether to generate `to_args`.
+    Enum(Vec<Variant>, bool),
+}
 
-    fn from_derive_input(input: &syn::DeriveInput) -> App {
+impl Input {
+    fn from_derive_input(input: &syn::DeriveInput) -> Input {
+        let settings = Settings::from_attributes(&input.attrs);
         match input.data {
             syn::Data::Struct(syn::DataStruct {
                 fields: syn::Fields::Named(ref fields),
                 ..
-            }) => App::from_named_fields(fields),
-            _ => {
-                abort!(
-                    input,
-                    "`#[derive(MiniClap)]` only works for non-tuple structs"
-                );
+            }) => Input::Struct(Box::new(App::from_named_fields(fields, &settings))),
+            syn::Data::Enum(_) if settings.validate.is_some() => {
+                abort!(settings.validate, "`validate` may only be used on a struct")
             }
-        }
+            syn::Data::Enum(_) if settings.augment.is_some() => {
+                abort!(settings.augment, "`augment` may only be used on a struct")
+            }
+            syn::Data::Enum(_) if settings.derive_default => {
+                abort!(input, "`derive_default` may only be used on a struct")
+            }
+            syn::Data::Enum(ref data) => {
+                let variants: Vec<Variant> = data
+                    .variants
+                    .iter()
+                    .map(|v| Variant::from_variant(v, &settings))
+                    .collect();
+                // Each name may only select one subcommand.
+                let mut names: BTreeMap<String, &Ident> = BTreeMap::new();
+                for v in &variants {
+                    if let Some(other) = names.insert(v.name.clone(), &v.ident) {
+                        abort!(
+                            v.ident,
+                            "Subcommand `{}` already used by `{}`",
+                            v.name,
+                            other
+                        );
+                    }
+                    for alias in v.aliases.iter().chain(&v.hidden_aliases) {
+                        if let Some(other) = names.insert(alias.value(), &v.ident) {
+                            abort!(
+                                alias,
+                                "Subcommand `{}` already used by `{}`",
+                                alias.value(),
+                                other
+                            );
+                        }
+                    }
+                }
+                Input::Enum(variants, settings.to_args)
+            }
+            _ => {
+                abort!(
+                    input,
+                    "`#[derive(MiniClap)]` only works for non-tuple structs and enums"
+                );
+            }
+        }
+    }
+}
+
+/// A subcommand, selected by the kebab-case name of the enum variant or any of its aliases.
+struct Variant {
+    ident: Ident,
+    name: String,
+    /// Other names for the subcommand, shown in `SUBCOMMANDS`.
+    aliases: Vec<LitStr>,
+    /// Other names for the subcommand that are left out of `SUBCOMMANDS`.
+    hidden_aliases: Vec<LitStr>,
+    kind: VariantKind,
+}
+
+enum VariantKind {
+    /// Arguments are declared inline as the variant's fields.
+    Fields(Box<App>),
+    /// Arguments are parsed by the single wrapped type, which must also derive `MiniClap`.
+    Wrapped,
+}
+
+impl Variant {
+    fn from_variant(variant: &syn::Variant, settings: &Settings) -> Variant {
+        let kind = match variant.fields {
+            syn::Fields::Named(ref fields) => {
+                VariantKind::Fields(Box::new(App::from_named_fields(fields, settings)))
+            }
+            syn::Fields::Unit => VariantKind::Fields(Box::default()),
+            syn::Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => VariantKind::Wrapped,
+            syn::Fields::Unnamed(ref fields) => {
+                abort!(fields, "Subcommand variants may only wrap a single type")
+            }
+        };
+        let mut aliases = Vec::new();
+        let mut hidden_aliases = Vec::new();
+        for meta in miniclap_metas(&variant.attrs) {
+            let attr_name = match meta.path().get_ident() {
+                Some(id) => id.to_string(),
+                None => abort!(meta.path(), "Invalid attribute name"),
+            };
+            let aliases = match attr_name.as_str() {
+                "alias" => &mut aliases,
+                "hidden_alias" => &mut hidden_aliases,
+                _ => abort!(meta.path(), "Unknown attribute"),
+            };
+            match &meta {
+                Meta::NameValue(syn::MetaNameValue {
+                    lit: Lit::Str(lit_str),
+                    ..
+                }) => aliases.push(lit_str.clone()),
+                _ => abort!(meta, "Attribute must be used as `{} = \"...\"`", attr_name),
+            }
+        }
+        Variant {
+            ident: variant.ident.clone(),
+            name: Variant::kebab_case(&variant.ident.to_string()),
+            aliases,
+            hidden_aliases,
+            kind,
+        }
+    }
+
+    fn kebab_case(ident: &str) -> String {
+        let mut name = String::new();
+        for (i, c) in ident.chars().enumerate() {
+            if c.is_uppercase() {
+                if i > 0 {
+                    name.push('-');
+                }
+                name.extend(c.to_lowercase());
+            } else {
+                name.push(if c == '_' { '-' } else { c });
+            }
+        }
+        name
+    }
+}
+
+/// A field whose type is another derived struct, with its arguments merged into this one.
+///
+/// The merged positionals follow the outer struct's own, in field order. Switches are only
+/// checked for clashes within each struct, and on a clash the outer struct's switch wins. A short
+/// that is a flag in one and an option in the other is taken as the flag, unless either struct
+/// sets `prefer_short_options`.
+struct Flattened {
+    name: Ident,
+    ty: syn::Type,
+}
+
+impl Flattened {
+    fn arg_var(&self) -> Ident {
+        format_ident!("arg_{}", &self.name)
+    }
+
+    fn var_ty(&self) -> TokenStream {
+        let ty = &self.ty;
+        quote! { <#ty as ::miniclap::Flatten>::Matches }
+    }
+
+    fn field(&self) -> TokenStream {
+        let (name, ty, arg_var) = (&self.name, &self.ty, self.arg_var());
+        quote! { #name: <#ty as ::miniclap::Flatten>::__build(#arg_var)? }
+    }
+
+    fn exclusive_field(&self) -> TokenStream {
+        let (name, ty, arg_var) = (&self.name, &self.ty, self.arg_var());
+        quote! { #name: <#ty as ::miniclap::Flatten>::__build_exclusive(#arg_var)? }
     }
 }
 
 struct Arg {
     name: Ident,
+    ty: syn::Type,
     index: Option<usize>,
     short: Option<char>,
     long: Option<String>,
+    negated_long: Option<String>,
     default_value: Option<Lit>,
+    /// Any further default values of a `Vec`, after `default_value`.
+    extra_default_values: Vec<Lit>,
+    range: Option<(LitStr, syn::ExprRange)>,
     is_flag: bool,
     is_required: bool,
     is_multiple: bool,
+    is_subcommand: bool,
+    is_raw: bool,
+    /// An empty value clears the values given so far.
+    is_clearable: bool,
+    /// Invalid UTF-8 is replaced rather than rejected.
+    is_lossy: bool,
+    /// Values are `std::time::Duration`s, which are parsed through `miniclap::DurationArg`.
+    is_duration: bool,
+    /// Values are `PathBuf`s or `OsString`s, which are kept as given even if not valid UTF-8.
+    is_os_string: bool,
+    /// A `Vec<(K, V)>` whose values are given as `key=value`, keeping their order and any
+    /// repeated keys. Holds `K` and `V`.
+    pair: Option<(syn::Type, syn::Type)>,
+    /// The environment variable read when the argument is not given on the command line.
+    env: Option<String>,
+    /// An integer flag that counts its occurrences, up to `count_max` if given.
+    is_count: bool,
+    /// A `count` of a `miniclap::CountLevel` type rather than an integer, which steps up through
+    /// the levels.
+    is_count_level: bool,
+    count_max: Option<syn::LitInt>,
+    /// Integer values are parsed in this base rather than 10.
+    number_base: Option<syn::LitInt>,
+    /// When given, no other argument may be, and required arguments are not checked.
+    is_exclusive: bool,
+    /// Each value is split at any of these characters, and empty parts are dropped.
+    value_delimiters: Option<LitStr>,
+    /// Takes every argument after `--`, which the positional arguments then never see.
+    is_trailing: bool,
+    /// Takes the positional arguments left over once the positional fields are filled.
+    is_remaining: bool,
+    /// A flag of a type other than `bool`, made from whether it was given with `From<bool>`.
+    is_coerced_flag: bool,
+    /// An `Option<bool>` flag, which is `Some(true)` when given, `Some(false)` when negated and
+    /// otherwise `None`.
+    is_tri_state: bool,
+    /// An array `[T; N]` positional, which takes the next `N` values. Holds `N` and `T`.
+    array_len: Option<(syn::LitInt, syn::Type)>,
+    /// The value must be attached to the switch rather than given as the next argument.
+    require_equals: bool,
+    /// Giving the argument reports a warning, with the note if there is one.
+    deprecated: Option<Option<LitStr>>,
+    /// Each value is passed through this before being parsed: `trim`, `uppercase`, `lowercase`
+    /// or the path of a `fn(String) -> String`.
+    transform: Option<LitStr>,
+    /// When not given, the value is asked for on the terminal with this message. Needs the
+    /// `prompt` feature of `miniclap`.
+    prompt: Option<LitStr>,
+    /// The option naming a config file of defaults for the other arguments.
+    is_config_file: bool,
+    /// The key of the value in the config file that the argument falls back to, if the struct has
+    /// a config file.
+    config_key: Option<String>,
+    is_optional_bool: bool,
+    default_missing_value: Option<LitStr>,
 }
 
 impl Arg {
@@ -225,44 +1563,494 @@ impl Arg {
         format_ident!("arg_{}", &self.name)
     }
 
-    fn declare(&self) -> TokenStream {
+    /// Holds the position at which the argument was last set, or 0 if it never was.
+    fn seq_var(&self) -> Ident {
+        format_ident!("seq_{}", &self.name)
+    }
+
+    /// The type of the variable holding the value between parsing and building.
+    fn var_ty(&self) -> TokenStream {
+        let ty = &self.ty;
+        if self.is_coerced_flag {
+            quote! { bool }
+        } else if self.is_count_level {
+            quote! { usize }
+        } else if let Some((_, elem)) = &self.array_len {
+            quote! { ::std::vec::Vec<#elem> }
+        } else if self.is_required && !self.is_multiple && self.default_value.is_none() {
+            quote! { ::std::option::Option<#ty> }
+        } else {
+            quote! { #ty }
+        }
+    }
+
+    /// Generates a `bool` expression testing whether any value of the argument displays as `value`.
+    fn value_eq(&self, value: &LitStr) -> TokenStream {
         let arg_var = self.arg_var();
-        if self.is_flag {
-            quote! { let mut #arg_var = false; }
-        } else if self.is_multiple {
-            quote! { let mut #arg_var = Vec::new(); }
+        let eq = quote! { ::std::string::ToString::to_string(value) == #value };
+        if (self.is_flag && !self.is_tri_state)
+            || (!self.is_multiple && self.default_value.is_some())
+        {
+            quote! {{
+                let value = &#arg_var;
+                #eq
+            }}
+        } else {
+            // Both `Vec` and `Option` iterate over the values that were given.
+            quote! { #arg_var.iter().any(|value| #eq) }
+        }
+    }
+
+    /// Whether the argument can be set from the environment or a config file.
+    fn has_fallback(&self) -> bool {
+        self.env.is_some() || self.config_key.is_some()
+    }
+
+    /// Whether the values of a `Vec` came from the environment or a config file, and so are
+    /// replaced rather than added to by the command line.
+    fn env_var(&self) -> Ident {
+        format_ident!("env_{}", &self.name)
+    }
+
+    /// Holds the position reserved for a value from the config file.
+    fn config_seq_var(&self) -> Ident {
+        format_ident!("config_seq_{}", &self.name)
+    }
+
+    /// Counts how many times a flag was given.
+    fn occ_var(&self) -> Ident {
+        format_ident!("occ_{}", &self.name)
+    }
+
+    /// Holds the position at which the `--no-` switch was last given, or 0 if it never was.
+    fn neg_var(&self) -> Ident {
+        format_ident!("neg_{}", &self.name)
+    }
+
+    fn initial(&self) -> TokenStream {
+        if self.is_count_level {
+            quote! { 0usize }
+        } else if self.is_count {
+            let ty = &self.ty;
+            quote! { <#ty as ::std::default::Default>::default() }
+        } else if self.is_tri_state {
+            quote! { None }
+        } else if self.is_flag {
+            quote! { false }
+        } else if self.is_multiple || self.array_len.is_some() {
+            quote! { Vec::new() }
         } else if let Some(lit) = &self.default_value {
-            quote! { let mut #arg_var = #lit; }
+            quote! { #lit }
         } else {
-            quote! { let mut #arg_var = None; }
+            quote! { None }
+        }
+    }
+
+    fn declare(&self) -> TokenStream {
+        let arg_var = self.arg_var();
+        let seq_var = self.seq_var();
+        let initial = self.initial();
+        let neg_decl = self.negated_long.as_ref().map(|_| {
+            let neg_var = self.neg_var();
+            quote! { let mut #neg_var = 0; }
+        });
+        let occ_decl = Some(self.occ_var())
+            .filter(|_| self.is_flag)
+            .map(|occ_var| quote! { let mut #occ_var: usize = 0; });
+        let env_decl = Some(self.env_var())
+            .filter(|_| self.is_multiple && self.has_fallback())
+            .map(|env_var| quote! { let mut #env_var = false; });
+        quote! {
+            let mut #arg_var = #initial;
+            let mut #seq_var = 0;
+            #neg_decl
+            #occ_decl
+            #env_decl
+        }
+    }
+
+    /// Generates statements that set the argument from its environment variable, if there is one.
+    /// These run before parsing, so that the command line takes precedence.
+    fn env_fallback(&self) -> Option<TokenStream> {
+        let env = self.env.as_ref()?;
+        let name_string = self.name.to_string();
+        Some(self.fallback(
+            quote! { sequence.next() },
+            quote! { ::miniclap::env_bool(#env) },
+            |ty| quote! { ::miniclap::parse_env::<#ty>(#name_string, #env)? },
+        ))
+    }
+
+    /// Like `env_fallback`, but for the value in the config file, which the environment takes
+    /// precedence over. The file is only known once the command line is parsed, so these run
+    /// afterwards, for arguments nothing else set. Each counts as given at the position reserved
+    /// by `reserve_config_seq`.
+    fn config_fallback(&self) -> Option<TokenStream> {
+        let key = self.config_key.as_ref()?;
+        let name_string = self.name.to_string();
+        let seq_var = self.seq_var();
+        let config_seq_var = self.config_seq_var();
+        let fallback = self.fallback(
+            quote! { #config_seq_var },
+            quote! { config.parse::<bool>(#name_string, #key)? },
+            |ty| quote! { config.parse::<#ty>(#name_string, #key)? },
+        );
+        Some(quote! {
+            if #seq_var == 0 {
+                #fallback
+            }
+        })
+    }
+
+    /// Generates a statement reserving the position a value from the config file counts as given
+    /// at. It comes before those of the environment and the command line, so that a later switch
+    /// such as `--no-` still overrides it.
+    fn reserve_config_seq(&self) -> Option<TokenStream> {
+        self.config_key.as_ref()?;
+        let config_seq_var = self.config_seq_var();
+        Some(quote! { let #config_seq_var = sequence.next(); })
+    }
+
+    /// Generates statements that take the value of the argument from the defaults given to
+    /// `try_parse_from_with_defaults` if nothing else set it.
+    fn supplied_default(&self) -> Option<TokenStream> {
+        if self.is_count
+            || self.is_raw
+            || self.is_trailing
+            || self.is_config_file
+            || self.array_len.is_some()
+        {
+            return None;
+        }
+        let name_string = self.name.to_string();
+        let seq_var = self.seq_var();
+        let fallback = self.fallback(
+            quote! { sequence.next() },
+            quote! { ::miniclap::supplied_default::<bool>(#name_string)? },
+            |ty| quote! { ::miniclap::supplied_default::<#ty>(#name_string)? },
+        );
+        Some(quote! {
+            if #seq_var == 0 {
+                #fallback
+            }
+        })
+    }
+
+    /// Generates statements that set the argument from a source other than the command line.
+    /// `seq` gives the position the value counts as given at, `get_bool` is an `Option<bool>`
+    /// expression for flags, and `parse` gives an `Option<T>` expression for a value of the
+    /// given type.
+    fn fallback(
+        &self,
+        seq: TokenStream,
+        get_bool: TokenStream,
+        parse: impl Fn(TokenStream) -> TokenStream,
+    ) -> TokenStream {
+        let arg_var = self.arg_var();
+        let seq_var = self.seq_var();
+        let name_string = self.name.to_string();
+        if self.is_tri_state {
+            quote! {
+                if let Some(value) = #get_bool {
+                    #arg_var = Some(value);
+                    #seq_var = #seq;
+                }
+            }
+        } else if self.is_flag {
+            // A false value leaves the flag unset, so that it does not count as given.
+            quote! {
+                if #get_bool == Some(true) {
+                    #arg_var = true;
+                    #seq_var = #seq;
+                }
+            }
+        } else if self.is_optional_bool {
+            quote! {
+                if let Some(value) = #get_bool {
+                    #arg_var = value;
+                    #seq_var = #seq;
+                }
+            }
+        } else if self.number_base.is_some() || self.pair.is_some() {
+            let convert = match &self.number_base {
+                Some(base) => quote! { ::miniclap::parse_radix(#name_string, &value, #base)? },
+                None => quote! { ::miniclap::parse_pair(#name_string, &value)? },
+            };
+            let store = self.store(quote! { value });
+            let mark = Some(self.env_var())
+                .filter(|_| self.is_multiple && self.has_fallback())
+                .map(|env_var| quote! { #env_var = true; });
+            let value = parse(quote! { String });
+            let replace = self.replace_env();
+            quote! {
+                if let Some(value) = #value {
+                    let value = #convert;
+                    #replace
+                    #store;
+                    #seq_var = #seq;
+                    #mark
+                }
+            }
+        } else {
+            let (value_ty, value) = self.parsed_value();
+            let store = self.store(value);
+            let mark = Some(self.env_var())
+                .filter(|_| self.is_multiple && self.has_fallback())
+                .map(|env_var| quote! { #env_var = true; });
+            let value = parse(value_ty);
+            // Values from a later source replace those from an earlier one.
+            let replace = self.replace_env();
+            quote! {
+                if let Some(value) = #value {
+                    #replace
+                    #store;
+                    #seq_var = #seq;
+                    #mark
+                }
+            }
+        }
+    }
+
+    /// Generates a statement storing `value` in the argument variable.
+    fn store(&self, value: TokenStream) -> TokenStream {
+        let arg_var = self.arg_var();
+        match (
+            self.is_multiple || self.array_len.is_some(),
+            &self.default_value,
+        ) {
+            (false, Some(_)) => quote! { #arg_var = #value },
+            (false, None) => quote! { #arg_var = Some(#value) },
+            (true, _) => quote! { #arg_var.push(#value) },
         }
     }
 
+    /// Generates a statement that drops values which came from the environment before the first
+    /// value from the command line is added.
+    fn replace_env(&self) -> Option<TokenStream> {
+        if !self.is_multiple || !self.has_fallback() {
+            return None;
+        }
+        let (arg_var, env_var) = (self.arg_var(), self.env_var());
+        Some(quote! {
+            if ::std::mem::take(&mut #env_var) {
+                #arg_var.clear();
+            }
+        })
+    }
+
+    /// Generates the handler for the `--no-` switch, if there is one.
+    fn negated_handler(&self) -> Option<TokenStream> {
+        let negated_long = self.negated_long.as_ref()?;
+        let name_string = self.name.to_string();
+        let neg_var = self.neg_var();
+        Some(quote! {
+            FlagHandler {
+                name: #name_string,
+                switch: Switch::Long(#negated_long),
+                is_exclusive: false,
+                assign: &FlagAssign::new(|| #neg_var = sequence.next()),
+            }
+        })
+    }
+
+    /// Generates code run right after parsing to let a later `--no-` switch clear the flag.
+    fn resolve_negation(&self) -> Option<TokenStream> {
+        self.negated_long.as_ref()?;
+        let arg_var = self.arg_var();
+        let seq_var = self.seq_var();
+        let neg_var = self.neg_var();
+        let value = if self.is_tri_state {
+            quote! { Some(false) }
+        } else {
+            quote! { false }
+        };
+        Some(quote! {
+            if #neg_var > #seq_var {
+                #arg_var = #value;
+                #seq_var = #neg_var;
+            }
+        })
+    }
+
     fn field(&self) -> TokenStream {
         let arg_var = self.arg_var();
-        let retrieve = if self.is_flag {
+        let retrieve = if self.is_coerced_flag {
+            quote! { ::std::convert::From::from(#arg_var) }
+        } else if self.is_count_level {
+            quote! { ::miniclap::count_level(#arg_var) }
+        } else if self.is_flag {
             quote! { #arg_var }
+        } else if self.is_subcommand && self.is_required {
+            quote! { #arg_var.ok_or_else(Error::missing_subcommand)? }
+        } else if let Some((len, _)) = &self.array_len {
+            let name_string = self.spelling();
+            // More values than fit would have gone to the next positional argument.
+            quote! {
+                match ::std::convert::TryFrom::try_from(#arg_var) {
+                    Ok(array) => array,
+                    Err(values) => {
+                        let values: Vec<_> = values;
+                        return Err(Error::missing_array_values(#name_string, #len, values.len()));
+                    }
+                }
+            }
         } else {
-            let name_string = self.name.to_string();
+            let name_string = self.spelling();
             match (self.is_multiple, &self.default_value, self.is_required) {
                 (false, Some(_), _) => quote! { #arg_var },
                 (_, None, false) => quote! { #arg_var },
                 (false, None, true) => quote! {
                     #arg_var.ok_or_else(|| Error::missing_required_argument(#name_string))?
                 },
-                (true, Some(lit), false) => quote! {{
+                (true, Some(lit), false) => {
+                    let extra = &self.extra_default_values;
+                    quote! {{
+                        if #arg_var.is_empty() {
+                            #arg_var.push(#lit);
+                            #(#arg_var.push(#extra);)*
+                        }
+                        #arg_var
+                    }}
+                }
+                (true, _, true) => quote! {{
                     if #arg_var.is_empty() {
-                        #arg_var.push(#lit);
+                        return Err(Error::missing_required_argument(#name_string));
                     }
                     #arg_var
                 }},
-                (true, _, true) => unreachable!("Currently no way to express multiple + required."),
             }
         };
         let name = &self.name;
         quote! { #name: #retrieve }
     }
 
+    /// The field when an exclusive flag was given, which leaves required arguments unchecked. It
+    /// is the value collected, or else the type's default for a required argument.
+    ///
+    /// With `checked`, the default is required of the type and spanned to the field, so a type
+    /// without one is reported there. Otherwise, as for a flattened struct that may never be used
+    /// with an exclusive flag, a type without one is reported missing when built.
+    fn exclusive_field(&self, checked: bool) -> TokenStream {
+        let (name, arg_var, ty) = (&self.name, self.arg_var(), &self.ty);
+        let or_default = |value: TokenStream| {
+            if checked {
+                return quote_spanned! {ty.span()=> ::miniclap::exclusive_default(#value) };
+            }
+            let missing = if self.is_subcommand {
+                quote! { Error::missing_subcommand() }
+            } else {
+                let name_string = self.spelling();
+                quote! { Error::missing_required_argument(#name_string) }
+            };
+            quote! {{
+                use ::miniclap::{HasDefault as _, NoDefault as _};
+                match #value {
+                    Some(value) => value,
+                    None => (&::miniclap::DefaultOf::<#ty>(::std::marker::PhantomData))
+                        .default_of()
+                        .ok_or_else(|| #missing)?,
+                }
+            }}
+        };
+        if self.array_len.is_some() {
+            let value = or_default(quote! { ::std::convert::TryFrom::try_from(#arg_var).ok() });
+            quote! { #name: #value }
+        } else if self.is_subcommand && self.is_required
+            || (self.is_required && !self.is_flag && !self.is_multiple)
+                && self.default_value.is_none()
+        {
+            let value = or_default(quote! { #arg_var });
+            quote! { #name: #value }
+        } else if self.is_multiple && self.is_required {
+            quote! { #name: #arg_var }
+        } else {
+            self.field()
+        }
+    }
+
+    /// The value of the field in the generated `Default` impl.
+    fn default_field(&self) -> TokenStream {
+        let name = &self.name;
+        let value = match (self.is_multiple, &self.default_value) {
+            _ if self.is_coerced_flag => quote! { ::std::convert::From::from(false) },
+            _ if self.is_count_level => quote! { ::miniclap::count_level(0) },
+            (false, Some(lit)) => quote! { #lit },
+            (true, Some(lit)) => {
+                let extra = &self.extra_default_values;
+                quote! { ::std::vec![#lit #(, #extra)*] }
+            }
+            (_, None) => quote! { ::std::default::Default::default() },
+        };
+        quote! { #name: #value }
+    }
+
+    fn check(&self) -> Option<TokenStream> {
+        let (lit_str, range) = self.range.as_ref()?;
+        let arg_var = self.arg_var();
+        let name_string = self.name.to_string();
+        let range_string = lit_str.value();
+        let check = quote! {
+            if !(#range).contains(value) {
+                return Err(Error::out_of_range(#name_string, value, #range_string));
+            }
+        };
+        Some(
+            match (self.is_multiple, &self.default_value, self.is_required) {
+                (true, _, _) => quote! {
+                    for value in &#arg_var {
+                        #check
+                    }
+                },
+                (false, Some(_), _) => quote! {{
+                    let value = &#arg_var;
+                    #check
+                }},
+                (false, None, _) => quote! {
+                    if let Some(value) = &#arg_var {
+                        #check
+                    }
+                },
+            },
+        )
+    }
+
+    /// Generates a statement reporting a warning if the argument is deprecated and was given.
+    fn warn_deprecated(&self) -> Option<TokenStream> {
+        let note = self.deprecated.as_ref()?;
+        let mut message = format!("Argument '{}' is deprecated", self.spelling());
+        if let Some(note) = note {
+            message = format!("{}: {}", message, note.value());
+        }
+        let seq_var = self.seq_var();
+        Some(quote! {
+            if #seq_var != 0 {
+                ::miniclap::warn(#message.to_string());
+            }
+        })
+    }
+
+    /// Generates a statement asking for the value if it was not given and there is a prompt.
+    fn prompt(&self) -> Option<TokenStream> {
+        let message = self.prompt.as_ref()?;
+        let arg_var = self.arg_var();
+        let name_string = self.spelling();
+        Some(quote! {
+            if #arg_var.is_none() {
+                #arg_var = ::miniclap::prompt(#name_string, #message)?;
+            }
+        })
+    }
+
+    /// How the argument is given on the command line, for errors: the long switch if there is
+    /// one, then the short switch, and otherwise the field name.
+    fn spelling(&self) -> String {
+        match (&self.short, &self.long) {
+            (_, Some(l)) => format!("--{}", l),
+            (Some(c), None) => format!("-{}", c),
+            (None, None) => self.name.to_string(),
+        }
+    }
+
     fn switch(&self) -> Option<TokenStream> {
         match (&self.short, &self.long) {
             (Some(c), Some(l)) => Some(quote! { Switch::Both(#c, #l) }),
@@ -274,15 +2062,155 @@ impl Arg {
 
     fn assign(&self) -> TokenStream {
         let arg_var = self.arg_var();
+        let seq_var = self.seq_var();
         if self.is_flag {
-            quote! { &FlagAssign::new(|| #arg_var = true) }
+            let occ_var = self.occ_var();
+            // Counts saturate rather than overflow, however many times the flag is given.
+            let set = match (self.is_count, &self.count_max) {
+                (true, Some(max)) => quote! { #arg_var = #arg_var.saturating_add(1).min(#max) },
+                (true, None) => quote! { #arg_var = #arg_var.saturating_add(1) },
+                (false, _) if self.is_tri_state => quote! { #arg_var = Some(true) },
+                (false, _) => quote! { #arg_var = true },
+            };
+            quote! {
+                &FlagAssign::new(|| {
+                    #set;
+                    #occ_var += 1;
+                    #seq_var = sequence.next();
+                })
+            }
+        } else if self.is_subcommand {
+            quote! {
+                &SubcommandAssign::new(|value| {
+                    #arg_var = Some(value);
+                    #seq_var = sequence.next();
+                })
+            }
+        } else if self.is_clearable {
+            let (value_ty, value) = self.parsed_value();
+            let replace_env = self.replace_env();
+            quote! {
+                &ClearableAssign::new(|value: Option<#value_ty>| {
+                    #replace_env
+                    match value {
+                        Some(value) => #arg_var.push(#value),
+                        None => #arg_var.clear(),
+                    }
+                    #seq_var = sequence.next();
+                })
+            }
         } else {
-            let store = match (self.is_multiple, &self.default_value) {
-                (false, Some(_)) => quote! { |value| #arg_var = value },
-                (false, None) => quote! { |value| #arg_var = Some(value) },
-                (true, _) => quote! { |value| #arg_var.push(value) },
+            let replace_env = self.replace_env();
+            let inner = if let Some(base) = &self.number_base {
+                let store = self.store(quote! { value });
+                quote! {
+                    ::miniclap::RadixAssign::new(#base, |value| {
+                        #replace_env
+                        #store;
+                        #seq_var = sequence.next();
+                    })
+                }
+            } else if self.pair.is_some() {
+                let store = self.store(quote! { value });
+                quote! {
+                    ::miniclap::PairAssign::new(|value| {
+                        #replace_env
+                        #store;
+                        #seq_var = sequence.next();
+                    })
+                }
+            } else if self.is_os_string {
+                let store = self.store(quote! { value });
+                quote! {
+                    ::miniclap::OsAssign::new(|value| {
+                        #replace_env
+                        #store;
+                        #seq_var = sequence.next();
+                    })
+                }
+            } else {
+                let (value_ty, value) = self.parsed_value();
+                let store = self.store(value);
+                quote! {
+                    ParsedAssign::new(|value: #value_ty| {
+                        #replace_env
+                        #store;
+                        #seq_var = sequence.next();
+                    })
+                }
+            };
+            let inner = match &self.transform {
+                Some(transform) => {
+                    let transform = match transform.value().as_str() {
+                        "trim" => quote! { |value: String| value.trim().to_string() },
+                        "uppercase" => quote! { |value: String| value.to_uppercase() },
+                        "lowercase" => quote! { |value: String| value.to_lowercase() },
+                        _ => {
+                            let path: syn::Path = transform.parse().unwrap();
+                            quote! { #path }
+                        }
+                    };
+                    quote! { ::miniclap::TransformAssign::new(#transform, #inner) }
+                }
+                None => inner,
             };
-            quote! { &ParsedAssign::new(#store) }
+            match &self.value_delimiters {
+                Some(delimiters) => {
+                    quote! { &::miniclap::DelimitedAssign::new(#delimiters, #inner) }
+                }
+                None => quote! { &#inner },
+            }
+        }
+    }
+
+    /// Generates the `ArgInfo` describing the argument.
+    fn info(&self) -> TokenStream {
+        let name_string = self.name.to_string();
+        let kind = if self.is_subcommand {
+            quote! { Subcommand }
+        } else if self.is_flag {
+            quote! { Flag }
+        } else if self.index.is_some() || self.is_trailing || self.is_remaining {
+            quote! { Positional }
+        } else {
+            quote! { Option }
+        };
+        let switch = match self.switch() {
+            Some(switch) => quote! { Some(::miniclap::#switch) },
+            None => quote! { None },
+        };
+        let is_required = self.is_required && self.default_value.is_none();
+        let is_multiple = self.is_multiple || self.is_count || self.array_len.is_some();
+        let env = match &self.env {
+            Some(env) => quote! { Some(#env) },
+            None => quote! { None },
+        };
+        let type_name = type_name(&self.ty);
+        let default_values = self
+            .default_value
+            .iter()
+            .chain(&self.extra_default_values)
+            .map(lit_string);
+        quote! {
+            ::miniclap::ArgInfo {
+                name: #name_string,
+                kind: ::miniclap::ArgKind::#kind,
+                switch: #switch,
+                is_required: #is_required,
+                is_multiple: #is_multiple,
+                env: #env,
+                type_name: #type_name,
+                default_values: &[#(#default_values),*],
+            }
+        }
+    }
+
+    /// The type that each value is parsed as, and an expression converting `value` from it.
+    fn parsed_value(&self) -> (TokenStream, TokenStream) {
+        if self.is_duration {
+            (quote! { ::miniclap::DurationArg }, quote! { value.0 })
+        } else {
+            (quote! { _ }, quote! { value })
         }
     }
 
@@ -290,96 +2218,1127 @@ impl Arg {
         let name_string = self.name.to_string();
         let switch = self.switch();
         let assign = self.assign();
-        if self.is_flag {
+        if self.is_subcommand {
+            quote! {
+                SubcommandHandler {
+                    name: #name_string,
+                    assign: #assign,
+                }
+            }
+        } else if self.is_flag {
+            let is_exclusive = self.is_exclusive;
             quote! {
                 FlagHandler {
                     name: #name_string,
                     switch: #switch,
+                    is_exclusive: #is_exclusive,
                     assign: #assign,
                 }
             }
-        } else if self.index.is_none() {
+        } else if self.index.is_none() && !self.is_trailing && !self.is_remaining {
+            let is_raw = self.is_raw;
+            let is_optional_bool = self.is_optional_bool;
+            let require_equals = self.require_equals;
+            let default_missing_value = match &self.default_missing_value {
+                Some(lit_str) => quote! { Some(#lit_str) },
+                None => quote! { None },
+            };
             quote! {
                 OptionHandler {
                     name: #name_string,
                     switch: #switch,
+                    is_raw: #is_raw,
+                    is_optional_bool: #is_optional_bool,
+                    default_missing_value: #default_missing_value,
+                    require_equals: #require_equals,
                     assign: #assign,
                 }
             }
         } else {
             let is_multiple = self.is_multiple;
+            let is_lossy = self.is_lossy;
             quote! {
                 PositionalHandler {
                     name: #name_string,
                     is_multiple: #is_multiple,
+                    is_lossy: #is_lossy,
                     assign: #assign,
                 }
             }
         }
     }
+
+    /// Generates an expression that formats `value`, a reference to one value, so that it parses
+    /// back to the same value.
+    fn format_value(&self, value: TokenStream) -> TokenStream {
+        if self.is_duration {
+            quote! { ::std::string::ToString::to_string(&::miniclap::DurationArg(*#value)) }
+        } else if self.is_os_string {
+            quote! {
+                ::std::convert::AsRef::<::std::ffi::OsStr>::as_ref(#value)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        } else if let Some(base) = &self.number_base {
+            quote! { ::miniclap::FromStrRadix::to_str_radix(#value, #base) }
+        } else if self.pair.is_some() {
+            quote! { ::std::format!("{}={}", #value.0, #value.1) }
+        } else {
+            quote! { ::std::string::ToString::to_string(#value) }
+        }
+    }
+
+    /// Generates statements that pass the field, given as the reference `value`, to `writer`.
+    /// Switches whose values equal their defaults are left out, as are prompted values, which
+    /// are usually secrets, and the config file, whose values are all written out already.
+    fn write_arg(&self, value: TokenStream) -> Option<TokenStream> {
+        if self.prompt.is_some() || self.is_config_file {
+            return None;
+        }
+        let spelling = self.spelling();
+        if self.is_subcommand {
+            return Some(if self.is_required {
+                quote! { writer.subcommand(#value.to_args()); }
+            } else {
+                quote! {
+                    if let Some(command) = #value {
+                        writer.subcommand(command.to_args());
+                    }
+                }
+            });
+        }
+        if self.is_count {
+            let count = if self.is_count_level {
+                quote! { ::miniclap::CountLevel::level_index(#value) }
+            } else {
+                quote! { *#value as usize }
+            };
+            return Some(quote! {
+                for _ in 0..#count {
+                    writer.flag(#spelling);
+                }
+            });
+        }
+        if self.is_tri_state {
+            let negated = self.negated_long.as_ref().map(|negated_long| {
+                let negated = format!("--{}", negated_long);
+                quote! { Some(false) => writer.flag(#negated), }
+            });
+            return Some(quote! {
+                match #value {
+                    Some(true) => writer.flag(#spelling),
+                    #negated
+                    _ => (),
+                }
+            });
+        }
+        if self.is_coerced_flag {
+            let ty = &self.ty;
+            return Some(quote! {
+                if *#value != <#ty as ::std::convert::From<bool>>::from(false) {
+                    writer.flag(#spelling);
+                }
+            });
+        }
+        if self.is_flag {
+            return Some(quote! {
+                if *#value {
+                    writer.flag(#spelling);
+                }
+            });
+        }
+
+        let format = self.format_value(quote! { value });
+        if self.is_trailing || self.is_raw {
+            let values = quote! { #value.iter().map(|value| #format).collect() };
+            return Some(if self.is_raw {
+                quote! { writer.raw(#spelling, #values); }
+            } else {
+                quote! { writer.trailing(#values); }
+            });
+        }
+        let is_positional = self.index.is_some() || self.is_remaining;
+        let write = if is_positional {
+            quote! { writer.positional(#format); }
+        } else if self.require_equals || self.is_optional_bool {
+            quote! { writer.attached(#spelling, #format); }
+        } else {
+            quote! { writer.option(#spelling, #format); }
+        };
+        Some(
+            match (
+                self.is_multiple || self.array_len.is_some(),
+                &self.default_value,
+            ) {
+                (true, Some(lit)) => {
+                    let extra = &self.extra_default_values;
+                    quote! {
+                        if *#value != ::std::vec![#lit #(, #extra)*] {
+                            for value in #value.iter() {
+                                #write
+                            }
+                        }
+                    }
+                }
+                (true, None) => quote! {
+                    for value in #value.iter() {
+                        #write
+                    }
+                },
+                // A positional is written even with its default value, so that the ones after
+                // it keep their places.
+                (false, Some(_)) if is_positional => quote! {{
+                    let value = #value;
+                    #write
+                }},
+                (false, Some(lit)) => quote! {
+                    if *#value != #lit {
+                        let value = #value;
+                        #write
+                    }
+                },
+                (false, None) if self.is_required => quote! {{
+                    let value = #value;
+                    #write
+                }},
+                (false, None) => quote! {
+                    if let Some(value) = #value {
+                        #write
+                    }
+                },
+            },
+        )
+    }
 }
 
 struct Generator {
     decls: Vec<TokenStream>,
+    env: Vec<TokenStream>,
+    /// Fallbacks to the config file, which only the top-level parse reads.
+    config: Vec<TokenStream>,
+    /// Reservations of the positions of values from the config file.
+    config_seqs: Vec<TokenStream>,
+    resolve: Vec<TokenStream>,
+    post: Vec<TokenStream>,
     fields: Vec<TokenStream>,
+    /// The fields when an exclusive flag was given.
+    exclusive_fields: Vec<TokenStream>,
+    /// The fields when an exclusive flag of an outer struct was given.
+    outer_exclusive_fields: Vec<TokenStream>,
+    /// The variables of the exclusive flags.
+    exclusive: Vec<Ident>,
     flags: Vec<TokenStream>,
     options: Vec<TokenStream>,
     positions: Vec<TokenStream>,
+    trailing: Option<TokenStream>,
+    overflow: Option<TokenStream>,
+    /// Handlers used more than once, bound ahead of the app so that they can be copied.
+    bindings: Vec<TokenStream>,
 }
 
 impl Generator {
     fn new() -> Generator {
         Generator {
             decls: Vec::new(),
+            env: Vec::new(),
+            config: Vec::new(),
+            config_seqs: Vec::new(),
+            resolve: Vec::new(),
+            post: Vec::new(),
             fields: Vec::new(),
+            exclusive_fields: Vec::new(),
+            outer_exclusive_fields: Vec::new(),
+            exclusive: Vec::new(),
             flags: Vec::new(),
             options: Vec::new(),
             positions: Vec::new(),
+            trailing: None,
+            overflow: None,
+            bindings: Vec::new(),
         }
     }
 
-    fn add_args(&mut self, args: &[Arg]) {
+    fn add_args<'a>(&mut self, args: impl IntoIterator<Item = &'a Arg>) {
         for arg in args {
             self.decls.push(arg.declare());
+            self.env.extend(arg.env_fallback());
+            self.config.extend(arg.config_fallback());
+            self.config_seqs.extend(arg.reserve_config_seq());
             self.fields.push(arg.field());
+            self.exclusive_fields.push(arg.exclusive_field(true));
+            self.outer_exclusive_fields.push(arg.exclusive_field(false));
+            if arg.is_exclusive {
+                self.exclusive.push(arg.arg_var());
+            }
+            if arg.is_subcommand {
+                continue;
+            }
             let handler = arg.handler();
             match (arg.is_flag, arg.index) {
+                _ if arg.is_trailing => self.trailing = Some(handler),
+                _ if arg.is_remaining => self.overflow = Some(handler),
+                // Each element is its own position, all sharing one handler.
+                _ if arg.array_len.is_some() => {
+                    let (len, _) = arg.array_len.as_ref().unwrap();
+                    let handler_var = format_ident!("handler_{}", &arg.name);
+                    self.bindings.push(quote! { let #handler_var = &#handler; });
+                    let len = len.base10_parse::<usize>().unwrap();
+                    for _ in 0..len {
+                        self.positions.push(quote! { *#handler_var });
+                    }
+                }
                 (true, _) => self.flags.push(handler),
                 (false, None) => self.options.push(handler),
                 (false, Some(_)) => self.positions.push(handler),
             }
+            self.flags.extend(arg.negated_handler());
+            self.resolve.extend(arg.resolve_negation());
+            self.resolve.extend(arg.supplied_default());
         }
     }
 
-    fn gen_impl(name: &Ident, app: &App) -> TokenStream {
+    /// Whichever of the two arguments was set last wins, and the other is reset.
+    fn add_overrides(&mut self, app: &App) {
+        let find = |ident: &Ident| {
+            app.by_switch
+                .iter()
+                .chain(&app.by_position)
+                .find(|arg| arg.name == *ident)
+                .unwrap()
+        };
+        for (a, b) in &app.overrides {
+            let (a, b) = (find(a), find(b));
+            let (a_var, a_seq, a_init) = (a.arg_var(), a.seq_var(), a.initial());
+            let (b_var, b_seq, b_init) = (b.arg_var(), b.seq_var(), b.initial());
+            self.post.push(quote! {
+                if #a_seq != 0 && #b_seq != 0 {
+                    if #a_seq > #b_seq {
+                        #b_var = #b_init;
+                        #b_seq = 0;
+                    } else {
+                        #a_var = #a_init;
+                        #a_seq = 0;
+                    }
+                }
+            });
+        }
+    }
+
+    /// Fills in each field that was not given from the first of its conditional defaults whose
+    /// condition holds. The field still counts as not given.
+    fn add_conditional_defaults(&mut self, app: &App) {
+        let find = |ident: &Ident| {
+            app.by_switch
+                .iter()
+                .chain(&app.by_position)
+                .chain(app.subcommand.as_deref())
+                .find(|arg| arg.name == *ident)
+                .unwrap()
+        };
+        let mut fields: Vec<&Ident> = app.conditional_defaults.iter().map(|d| &d.field).collect();
+        fields.dedup();
+        for field in fields {
+            let field = find(field);
+            let seq_var = field.seq_var();
+            let branches = app
+                .conditional_defaults
+                .iter()
+                .filter(|d| d.field == field.name)
+                .map(|d| {
+                    let other = find(&d.other);
+                    let condition = match &d.value {
+                        Some(value) => other.value_eq(value),
+                        None => {
+                            let other_seq = other.seq_var();
+                            quote! { #other_seq != 0 }
+                        }
+                    };
+                    let default = &d.default;
+                    let store = field.store(quote! { #default });
+                    quote! { if #condition { #store; } }
+                });
+            self.post.push(quote! {
+                if #seq_var == 0 {
+                    #(#branches)else*
+                }
+            });
+        }
+    }
+
+    fn add_requirements(&mut self, app: &App) {
+        let find = |ident: &Ident| {
+            app.by_switch
+                .iter()
+                .chain(&app.by_position)
+                .chain(app.subcommand.as_deref())
+                .find(|arg| arg.name == *ident)
+                .unwrap()
+        };
+        for requirement in &app.requirements {
+            self.post.push(match requirement {
+                Requirement::IfEq {
+                    field,
+                    other,
+                    value,
+                } => {
+                    let (field, other) = (find(field), find(other));
+                    let seq_var = field.seq_var();
+                    let value_eq = other.value_eq(value);
+                    let (name_string, other_string) =
+                        (field.name.to_string(), other.name.to_string());
+                    quote! {
+                        if #seq_var == 0 && #value_eq {
+                            return Err(Error::missing_required_if_eq(
+                                #name_string,
+                                #other_string,
+                                #value,
+                            ));
+                        }
+                    }
+                }
+                Requirement::All { field, others } => {
+                    let field = find(field);
+                    let seq_var = field.seq_var();
+                    let name_string = field.name.to_string();
+                    let checks = others.iter().map(|other| {
+                        let other = find(other);
+                        let other_seq = other.seq_var();
+                        let other_string = other.name.to_string();
+                        quote! {
+                            if #other_seq == 0 {
+                                return Err(Error::missing_requirement(#name_string, #other_string));
+                            }
+                        }
+                    });
+                    quote! {
+                        if #seq_var != 0 {
+                            #(#checks)*
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    fn add_groups(&mut self, app: &App) {
+        let find = |ident: &Ident| {
+            app.by_switch
+                .iter()
+                .chain(&app.by_position)
+                .chain(app.subcommand.as_deref())
+                .find(|arg| arg.name == *ident)
+                .unwrap()
+        };
+        for group in &app.groups {
+            let name = &group.name;
+            let members: Vec<String> = group.members.iter().map(Ident::to_string).collect();
+            let seq_vars = group.members.iter().map(|member| find(member).seq_var());
+            let too_many = Some(quote! {
+                if given > 1 {
+                    return Err(Error::group_conflict(#name, &[#(#members),*]));
+                }
+            })
+            .filter(|_| !group.multiple);
+            let missing = Some(quote! {
+                if given == 0 {
+                    return Err(Error::missing_group(#name, &[#(#members),*]));
+                }
+            })
+            .filter(|_| group.required);
+            self.post.push(quote! {{
+                let given = 0 #(+ usize::from(#seq_vars != 0))*;
+                #too_many
+                #missing
+            }});
+        }
+    }
+
+    fn add_deprecations(&mut self, app: &App) {
+        let args = app.by_switch.iter().chain(&app.by_position);
+        let args = args.chain(app.subcommand.as_deref());
+        self.post.extend(args.filter_map(Arg::warn_deprecated));
+    }
+
+    fn add_prompts(&mut self, args: &[Arg]) {
+        self.post.extend(args.iter().filter_map(Arg::prompt));
+    }
+
+    fn add_checks(&mut self, args: &[Arg]) {
+        self.post.extend(args.iter().filter_map(Arg::check));
+    }
+
+    /// Flattened matches are filled in while parsing and always set once it succeeds.
+    fn add_flattened(&mut self, flattened: &[Flattened]) {
+        for f in flattened {
+            let arg_var = f.arg_var();
+            self.decls.push(quote! { let mut #arg_var = None; });
+            self.resolve
+                .push(quote! { let #arg_var = #arg_var.unwrap(); });
+            self.fields.push(f.field());
+            self.exclusive_fields.push(f.exclusive_field());
+            self.outer_exclusive_fields.push(f.exclusive_field());
+        }
+    }
+
+    fn from_app(app: &App) -> Generator {
         let mut this = Generator::new();
         this.add_args(&app.by_switch);
         this.add_args(&app.by_position);
-        let decls = &this.decls;
-        let fields = &this.fields;
-        let flags = &this.flags;
-        let options = &this.options;
-        let positions = &this.positions;
-        quote!(
-            impl ::miniclap::MiniClap for #name {
-                fn __parse_internal(args: ::miniclap::ArgOsIterator) -> ::miniclap::Result<Self> {
-                    use ::miniclap::{FlagHandler, OptionHandler, PositionalHandler};
-                    use ::miniclap::{Error, Switch, FlagAssign, ParsedAssign};
-                    use ::std::vec::Vec;
-                    use ::std::option::Option::{Some, None};
+        this.add_args(app.subcommand.as_deref());
+        this.add_flattened(&app.flattened);
+        this.add_overrides(app);
+        this.add_deprecations(app);
+        this.add_conditional_defaults(app);
+        this.add_prompts(&app.by_switch);
+        this.add_requirements(app);
+        this.add_groups(app);
+        this.add_checks(&app.by_switch);
+        this.add_checks(&app.by_position);
+        this
+    }
+
+    /// Generates statements that declare the argument variables and fill them from `args`.
+    fn gen_collect(&self, app: &App) -> TokenStream {
+        let decls = &self.decls;
+        let resolve = &self.resolve;
+        let flags = &self.flags;
+        let options = &self.options;
+        let positions = &self.positions;
+        let subcommand = Generator::gen_subcommand(app);
+        let trailing = self.gen_trailing();
+        let unknown_as_positional = app.unknown_as_positional;
+        let allow_negative_numbers = app.allow_negative_numbers;
+        let allow_space_around_equals = app.allow_space_around_equals;
+        let ignore_extra_positionals = app.ignore_extra_positionals;
+        let prefer_short_options = app.prefer_short_options;
+        let max_args = match &app.max_args {
+            Some(lit_int) => quote! { Some(#lit_int) },
+            None => quote! { None },
+        };
+        // Arguments added by `augment` go after all of the derived ones, flattened or not, so
+        // that they can be checked against every switch.
+        let (augment, parse) = match &app.augment {
+            Some(path) => (
+                Some(quote! {
+                    let augment = &mut ::miniclap::AppBuilder::default();
+                    #path(augment);
+                }),
+                quote! { augment.__parse(args, app) },
+            ),
+            None => (None, quote! { ::miniclap::parse_args(args, app) }),
+        };
+        let parse = Generator::gen_flattened_parse(&app.flattened, parse);
+        let env = &self.env;
+        let bindings = &self.bindings;
+        let config_seqs = &self.config_seqs;
+        let config = self.gen_config(app);
+        quote! {
+            let sequence = &Sequence::new();
+            #(#decls)*
+            #(#config_seqs)*
+            #(#env)*
+            #subcommand
+            #trailing
+            #(#bindings)*
+            #augment
+
+            let app = &::miniclap::App {
+                flags: &[ #(#flags),* ],
+                options: &[ #(#options),* ],
+                positions: &[ #(#positions),* ],
+                subcommand,
+                overflow,
+                trailing,
+                unknown_as_positional: #unknown_as_positional,
+                allow_negative_numbers: #allow_negative_numbers,
+                allow_space_around_equals: #allow_space_around_equals,
+                ignore_extra_positionals: #ignore_extra_positionals,
+                prefer_short_options: #prefer_short_options,
+                max_args: #max_args,
+                batch_end: None,
+            };
+            #parse?;
 
-                    #(#decls)*
+            #config
+            #(#resolve)*
+        }
+    }
+
+    /// Like `gen_collect`, but appends the handlers to those of `outer` and passes the merged app
+    /// to `parse` instead of parsing `args` directly.
+    fn gen_collect_flattened(&self, app: &App) -> TokenStream {
+        let decls = &self.decls;
+        let resolve = &self.resolve;
+        let flags = &self.flags;
+        let options = &self.options;
+        let positions = &self.positions;
+        let subcommand = Generator::gen_subcommand(app);
+        let trailing = self.gen_trailing();
+        let unknown_as_positional = app.unknown_as_positional;
+        let allow_negative_numbers = app.allow_negative_numbers;
+        let allow_space_around_equals = app.allow_space_around_equals;
+        let ignore_extra_positionals = app.ignore_extra_positionals;
+        let prefer_short_options = app.prefer_short_options;
+        let max_args = match &app.max_args {
+            Some(lit_int) => quote! { Some(#lit_int) },
+            None => quote! { None },
+        };
+        let parse = Generator::gen_flattened_parse(&app.flattened, quote! { parse(app) });
+        let env = &self.env;
+        let bindings = &self.bindings;
+        quote! {
+            #(#decls)*
+            #(#env)*
+            #subcommand
+            #trailing
+            #(#bindings)*
 
-                    ::miniclap::parse_args(args, &::miniclap::App {
-                        flags: &[ #(#flags),* ],
-                        options: &[ #(#options),* ],
-                        positions: &[ #(#positions),* ],
-                    })?;
+            let flags = [ #(#flags),* ];
+            let options = [ #(#options),* ];
+            let positions = [ #(#positions),* ];
+            let flags: Vec<_> = outer.flags.iter().chain(&flags).copied().collect();
+            let options: Vec<_> = outer.options.iter().chain(&options).copied().collect();
+            let positions: Vec<_> = outer.positions.iter().chain(&positions).copied().collect();
+            let app = &::miniclap::App {
+                flags: &flags,
+                options: &options,
+                positions: &positions,
+                subcommand: outer.subcommand.or(subcommand),
+                overflow: outer.overflow.or(overflow),
+                trailing: outer.trailing.or(trailing),
+                unknown_as_positional: outer.unknown_as_positional || #unknown_as_positional,
+                allow_negative_numbers: outer.allow_negative_numbers || #allow_negative_numbers,
+                allow_space_around_equals: outer.allow_space_around_equals
+                    || #allow_space_around_equals,
+                ignore_extra_positionals: outer.ignore_extra_positionals
+                    || #ignore_extra_positionals,
+                prefer_short_options: outer.prefer_short_options || #prefer_short_options,
+                max_args: outer.max_args.or(#max_args),
+                batch_end: outer.batch_end,
+            };
+            #parse?;
+
+            #(#resolve)*
+        }
+    }
+
+    /// Generates statements binding `trailing` to the optional handler for arguments after `--`,
+    /// and `overflow` to that for left over positional arguments.
+    fn gen_trailing(&self) -> TokenStream {
+        let bind = |var: Ident, handler: &Option<TokenStream>| match handler {
+            Some(handler) => quote! {
+                let #var = &#handler;
+                let #var = Some(*#var);
+            },
+            None => quote! { let #var = None; },
+        };
+        let trailing = bind(format_ident!("trailing"), &self.trailing);
+        let overflow = bind(format_ident!("overflow"), &self.overflow);
+        quote! {
+            #trailing
+            #overflow
+        }
+    }
+
+    /// Generates statements that read the config file given on the command line or in the
+    /// environment, once parsing has found it, and set the arguments it has values for.
+    fn gen_config(&self, app: &App) -> Option<TokenStream> {
+        app.config_file.as_ref()?;
+        let arg = app.by_switch.iter().find(|arg| arg.is_config_file)?;
+        let (arg_var, seq_var) = (arg.arg_var(), arg.seq_var());
+        let path = match &arg.default_value {
+            Some(_) => quote! { Some(&#arg_var) },
+            None => quote! { #arg_var.as_ref() },
+        };
+        let config = &self.config;
+        // A default path is not read, as the file is only wanted when one is given.
+        Some(quote! {
+            let config = match #path {
+                Some(path) if #seq_var != 0 => {
+                    ::miniclap::ConfigFile::load(::std::convert::AsRef::as_ref(path))?
+                }
+                _ => ::miniclap::ConfigFile::default(),
+            };
+            #(#config)*
+        })
+    }
+
+    /// Generates a statement binding `subcommand` to the optional subcommand handler.
+    fn gen_subcommand(app: &App) -> TokenStream {
+        match &app.subcommand {
+            Some(arg) => {
+                let handler = arg.handler();
+                // Borrowed first so that the handler's temporaries live as long as `app`.
+                quote! {
+                    let subcommand = &#handler;
+                    let subcommand = Some(*subcommand);
+                }
+            }
+            None => quote! { let subcommand = None; },
+        }
+    }
+
+    /// Generates an expression that runs `parse` with `app` merged with each flattened struct's
+    /// handlers in turn, storing each struct's matches.
+    fn gen_flattened_parse(flattened: &[Flattened], parse: TokenStream) -> TokenStream {
+        flattened.iter().rev().fold(parse, |inner, f| {
+            let (ty, arg_var) = (&f.ty, f.arg_var());
+            quote! {{
+                #arg_var = Some(<#ty as ::miniclap::Flatten>::__collect_with(
+                    app,
+                    sequence,
+                    &mut |app: &::miniclap::App| #inner,
+                )?);
+                ::miniclap::Result::Ok(())
+            }}
+        })
+    }
+
+    /// Generates statements that validate the argument variables and build `ctor { ... }`.
+    fn gen_build(&self, ctor: TokenStream) -> TokenStream {
+        let post = &self.post;
+        let fields = &self.fields;
+        // The parser has already checked that nothing else was given with an exclusive flag, so
+        // the fields are built without checking that required ones were given.
+        let exclusive = self.exclusive.iter().map(|arg_var| {
+            let build = self.gen_build_exclusive(ctor.clone(), true);
+            quote! {
+                if #arg_var {
+                    return #build;
+                }
+            }
+        });
+        quote! {
+            #(#exclusive)*
+            #(#post)*
+
+            Ok(#ctor {
+                #(#fields),*
+            })
+        }
+    }
+
+    /// Generates an expression building `ctor { ... }` as when an exclusive flag was given, of
+    /// this struct if `own` and otherwise of an outer one.
+    fn gen_build_exclusive(&self, ctor: TokenStream, own: bool) -> TokenStream {
+        let fields = if own {
+            &self.exclusive_fields
+        } else {
+            &self.outer_exclusive_fields
+        };
+        quote! {
+            Ok(#ctor {
+                #(#fields),*
+            })
+        }
+    }
+
+    /// Generates an expression that parses `args` into `ctor { ... }`.
+    fn gen_parse(ctor: TokenStream, app: &App) -> TokenStream {
+        let this = Generator::from_app(app);
+        let collect = this.gen_collect(app);
+        let build = this.gen_build(ctor);
+        quote! {{
+            #collect
+            #build
+        }}
+    }
 
+    /// Generates statements that pass the arguments to `writer`, getting a reference to each field
+    /// from `field`. The positionals of flattened fields follow the struct's own, and then come any
+    /// remaining ones.
+    fn gen_write_args(app: &App, field: impl Fn(&Ident) -> TokenStream) -> TokenStream {
+        let (remaining, switches): (Vec<&Arg>, Vec<&Arg>) =
+            app.by_switch.iter().partition(|arg| arg.is_remaining);
+        let write = |args: Vec<&Arg>| -> Vec<TokenStream> {
+            args.into_iter()
+                .filter_map(|arg| arg.write_arg(field(&arg.name)))
+                .collect()
+        };
+        let switches = write(switches);
+        let positions = write(app.by_position.iter().collect());
+        let remaining = write(remaining);
+        let subcommand = write(app.subcommand.as_deref().into_iter().collect());
+        let flattened = app.flattened.iter().map(|f| field(&f.name));
+        quote! {
+            #(#switches)*
+            #(#positions)*
+            #(#flattened.__write_args(writer);)*
+            #(#remaining)*
+            #(#subcommand)*
+        }
+    }
+
+    fn gen_uses() -> TokenStream {
+        quote! {
+            use ::miniclap::{FlagHandler, OptionHandler, PositionalHandler, SubcommandHandler};
+            use ::miniclap::{Error, Switch, FlagAssign, ParsedAssign, SubcommandAssign, Sequence};
+            use ::miniclap::ClearableAssign;
+            use ::std::vec::Vec;
+            use ::std::option::Option::{Some, None};
+        }
+    }
+
+    fn gen_struct_impl(input: &syn::DeriveInput, app: &App) -> TokenStream {
+        let name = &input.ident;
+        let vis = &input.vis;
+        // Without `matches`, the type is only needed by `Flatten`, and is kept out of the way in
+        // an unnamed scope.
+        let matches = if app.matches {
+            format_ident!("{}Matches", name)
+        } else {
+            format_ident!("__Matches")
+        };
+        let uses = Generator::gen_uses();
+        let this = Generator::from_app(app);
+        let collect = this.gen_collect(app);
+        let collect_flattened = this.gen_collect_flattened(app);
+        let build = this.gen_build(quote! { #name });
+        let build_exclusive = this.gen_build_exclusive(quote! { #name }, false);
+
+        let args: Vec<&Arg> = app
+            .by_switch
+            .iter()
+            .chain(&app.by_position)
+            .chain(app.subcommand.as_deref())
+            .collect();
+        let arg_vars: Vec<Ident> = args.iter().map(|arg| arg.arg_var()).collect();
+        let spec = args.iter().map(|arg| arg.info());
+        let seq_vars: Vec<Ident> = args.iter().map(|arg| arg.seq_var()).collect();
+        let var_tys: Vec<TokenStream> = args.iter().map(|arg| arg.var_ty()).collect();
+        let flat_vars: Vec<Ident> = app.flattened.iter().map(|f| f.arg_var()).collect();
+        let flat_tys: Vec<TokenStream> = app.flattened.iter().map(|f| f.var_ty()).collect();
+        let occ_vars: Vec<Ident> = args
+            .iter()
+            .filter(|arg| arg.is_flag)
+            .map(|arg| arg.occ_var())
+            .collect();
+        let occ_getters = args.iter().filter(|arg| arg.is_flag).map(|arg| {
+            let occ_var = arg.occ_var();
+            let getter = format_ident!("{}_occurrences", arg.name);
+            let doc = format!("How many times the `{}` flag was given.", arg.name);
+            quote! {
+                #[doc = #doc]
+                #vis fn #getter(&self) -> usize {
+                    self.#occ_var
+                }
+            }
+        });
+        let getters = args
+            .iter()
+            .map(|arg| (&arg.name, arg.arg_var(), arg.var_ty()));
+        let flat_getters = app
+            .flattened
+            .iter()
+            .map(|f| (&f.name, f.arg_var(), f.var_ty()));
+        let getters = getters
+            .chain(flat_getters)
+            .map(|(getter, arg_var, var_ty)| {
+                let getter_mut = format_ident!("{}_mut", getter);
+                let doc = format!("The value collected for `{}`.", getter);
+                quote! {
+                    #[doc = #doc]
+                    #vis fn #getter(&self) -> &#var_ty {
+                        &self.#arg_var
+                    }
+
+                    #[doc = #doc]
+                    #vis fn #getter_mut(&mut self) -> &mut #var_ty {
+                        &mut self.#arg_var
+                    }
+                }
+            });
+        let default_impl = if app.derive_default {
+            let arg_fields = args.iter().map(|arg| arg.default_field());
+            let flat_fields = app.flattened.iter().map(|f| &f.name);
+            Some(quote! {
+                impl ::std::default::Default for #name {
+                    fn default() -> Self {
+                        Self {
+                            #(#arg_fields,)*
+                            #(#flat_fields: ::std::default::Default::default(),)*
+                        }
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        let to_args_impl = if app.to_args {
+            let write = Generator::gen_write_args(app, |name| quote! { (&self.#name) });
+            Some(quote! {
+                impl #name {
+                    /// Writes the value back out as arguments that parse to it again, such as to
+                    /// log or rerun the command. Switches come first, using the long name if there
+                    /// is one, and those with their default values are left out.
+                    #vis fn to_args(&self) -> ::std::vec::Vec<::std::string::String> {
+                        let mut writer = ::miniclap::ArgWriter::default();
+                        self.__write_args(&mut writer);
+                        writer.finish()
+                    }
+
+                    #[doc(hidden)]
+                    #vis fn __write_args(&self, writer: &mut ::miniclap::ArgWriter) {
+                        #write
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        let validate = app.validate.as_ref().map(|path| {
+            quote! {
+                fn validate(&self) -> ::miniclap::Result<()> {
+                    #path(self)
+                }
+            }
+        });
+        let augment = app.augment.as_ref().map(|path| {
+            quote! {
+                fn augment(app: &mut ::miniclap::AppBuilder) {
+                    #path(app)
+                }
+            }
+        });
+        let matches_api = if app.matches {
+            Some(quote! {
+                impl #name {
+                    /// Parses the arguments without building the final value, so they can be
+                    /// inspected or adjusted first.
+                    #vis fn matches_from<I, T>(args: I) -> ::miniclap::Result<#matches>
+                    where
+                        I: ::std::iter::IntoIterator<Item = T>,
+                        T: ::std::convert::Into<::std::ffi::OsString>,
+                    {
+                        let args = &mut args.into_iter().map(|x| x.into());
+                        let _bin_name = args.next();
+                        #matches::__collect(args)
+                    }
+                }
+
+                impl #matches {
+                    #(#getters)*
+                    #(#occ_getters)*
+                }
+            })
+        } else {
+            None
+        };
+        let matches_attrs = if app.matches {
+            let doc = format!(
+                "Arguments collected for [`{}`] before defaults are applied and validated.",
+                name
+            );
+            quote! { #[doc = #doc] }
+        } else {
+            quote! { #[doc(hidden)] }
+        };
+
+        let output = quote!(
+            #matches_attrs
+            #[allow(dead_code)]
+            #vis struct #matches {
+                #(#arg_vars: #var_tys,)*
+                #(#seq_vars: usize,)*
+                #(#occ_vars: usize,)*
+                #(#flat_vars: #flat_tys,)*
+            }
+
+            #matches_api
+
+            impl #matches {
+                #[allow(unused_variables, unused_assignments)]
+                fn __collect(args: ::miniclap::ArgOsIterator) -> ::miniclap::Result<Self> {
+                    #uses
+                    #collect
                     Ok(Self {
-                        #(#fields),*
+                        #(#arg_vars,)*
+                        #(#seq_vars,)*
+                        #(#occ_vars,)*
+                        #(#flat_vars,)*
                     })
                 }
+
+                #[allow(unused_variables, unused_assignments)]
+                fn __collect_with(
+                    outer: &::miniclap::App,
+                    sequence: &::miniclap::Sequence,
+                    parse: &mut dyn FnMut(&::miniclap::App) -> ::miniclap::Result<()>,
+                ) -> ::miniclap::Result<Self> {
+                    #uses
+                    #collect_flattened
+                    Ok(Self {
+                        #(#arg_vars,)*
+                        #(#seq_vars,)*
+                        #(#occ_vars,)*
+                        #(#flat_vars,)*
+                    })
+                }
+
+                /// Applies defaults and validation to build the final value.
+                #[allow(unused_variables, unused_assignments)]
+                #vis fn into_opts(self) -> ::miniclap::Result<#name> {
+                    #uses
+                    let Self {
+                        #(mut #arg_vars,)*
+                        #(mut #seq_vars,)*
+                        #(#flat_vars,)*
+                        ..
+                    } = self;
+                    #build
+                }
+
+                #[allow(unused_variables, unused_assignments, unused_mut)]
+                fn __into_exclusive(self) -> ::miniclap::Result<#name> {
+                    #uses
+                    let Self {
+                        #(mut #arg_vars,)*
+                        #(#flat_vars,)*
+                        ..
+                    } = self;
+                    #build_exclusive
+                }
+            }
+
+            impl ::miniclap::MiniClap for #name {
+                const SPEC: &'static [::miniclap::ArgInfo] = &[ #(#spec),* ];
+
+                fn __parse_internal(args: ::miniclap::ArgOsIterator) -> ::miniclap::Result<Self> {
+                    #matches::__collect(args)?.into_opts()
+                }
+
+                #validate
+
+                #augment
+            }
+
+            #default_impl
+
+            #to_args_impl
+
+            impl ::miniclap::Flatten for #name {
+                type Matches = #matches;
+
+                fn __collect_with(
+                    outer: &::miniclap::App,
+                    sequence: &::miniclap::Sequence,
+                    parse: &mut dyn FnMut(&::miniclap::App) -> ::miniclap::Result<()>,
+                ) -> ::miniclap::Result<#matches> {
+                    #matches::__collect_with(outer, sequence, parse)
+                }
+
+                fn __build(matches: #matches) -> ::miniclap::Result<Self> {
+                    matches.into_opts()
+                }
+
+                fn __build_exclusive(matches: #matches) -> ::miniclap::Result<Self> {
+                    matches.__into_exclusive()
+                }
+            }
+        );
+        if app.matches {
+            output
+        } else {
+            quote! {
+                const _: () = {
+                    #output
+                };
+            }
+        }
+    }
+
+    fn gen_enum_impl(input: &syn::DeriveInput, variants: &[Variant], to_args: bool) -> TokenStream {
+        let (name, vis) = (&input.ident, &input.vis);
+        let uses = Generator::gen_uses();
+        let arms = variants.iter().map(|v| {
+            let ident = &v.ident;
+            let name = &v.name;
+            let parse = match &v.kind {
+                VariantKind::Fields(app) => Generator::gen_parse(quote! { Self::#ident }, app),
+                VariantKind::Wrapped => quote! {
+                    ::miniclap::MiniClap::__parse_internal(args).map(Self::#ident)
+                },
+            };
+            let aliases = v.aliases.iter().chain(&v.hidden_aliases);
+            quote! { #name #(| #aliases)* => #parse, }
+        });
+        let subcommands = variants.iter().map(|v| {
+            let (name, aliases) = (&v.name, &v.aliases);
+            quote! {
+                ::miniclap::SubcommandInfo {
+                    name: #name,
+                    aliases: &[#(#aliases),*],
+                }
+            }
+        });
+        let to_args_impl = if to_args {
+            let arms = variants.iter().map(|v| {
+                let ident = &v.ident;
+                let name = &v.name;
+                match &v.kind {
+                    VariantKind::Fields(app) => {
+                        let fields = app
+                            .by_switch
+                            .iter()
+                            .chain(&app.by_position)
+                            .chain(app.subcommand.as_deref())
+                            .map(|arg| &arg.name)
+                            .chain(app.flattened.iter().map(|f| &f.name));
+                        let write = Generator::gen_write_args(app, |name| quote! { #name });
+                        quote! {
+                            Self::#ident { #(#fields,)* .. } => {
+                                let mut writer = ::miniclap::ArgWriter::default();
+                                #write
+                                (#name, writer.finish())
+                            }
+                        }
+                    }
+                    VariantKind::Wrapped => quote! {
+                        Self::#ident(inner) => (#name, inner.to_args()),
+                    },
+                }
+            });
+            Some(quote! {
+                impl #name {
+                    /// Writes the subcommand name followed by its arguments, which parse back to
+                    /// the same value.
+                    #[allow(unused_variables)]
+                    #vis fn to_args(&self) -> ::std::vec::Vec<::std::string::String> {
+                        let (name, args) = match self {
+                            #(#arms)*
+                        };
+                        let mut all = ::std::vec![::std::string::ToString::to_string(name)];
+                        all.extend(args);
+                        all
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        quote!(
+            #to_args_impl
+
+            impl ::miniclap::MiniClap for #name {
+                const SUBCOMMANDS: &'static [::miniclap::SubcommandInfo] = &[ #(#subcommands),* ];
+
+                fn __parse_internal(args: ::miniclap::ArgOsIterator) -> ::miniclap::Result<Self> {
+                    ::miniclap::parse_subcommand(args)
+                }
+            }
+
+            impl ::miniclap::Subcommand for #name {
+                #[allow(unused_variables, unused_assignments)]
+                fn __parse_subcommand(
+                    name: &str,
+                    args: ::miniclap::ArgOsIterator,
+                ) -> ::miniclap::Result<Self> {
+                    #uses
+                    match name {
+                        #(#arms)*
+                        _ => Err(Error::unknown_subcommand(name)),
+                    }
+                }
             }
         )
     }
@@ -389,7 +3348,45 @@ impl Generator {
 #[proc_macro_error]
 pub fn derive_miniclap(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input: syn::DeriveInput = syn::parse_macro_input!(input);
-    let app = App::from_derive_input(&input);
+    match Input::from_derive_input(&input) {
+        Input::Struct(app) => Generator::gen_struct_impl(&input, &app),
+        Input::Enum(variants, to_args) => Generator::gen_enum_impl(&input, &variants, to_args),
+    }
+    .into()
+}
+
+/// Implements `miniclap::CountLevel` for an enum without fields, with its variants as the levels
+/// in declaration order.
+#[proc_macro_derive(CountLevel)]
+#[proc_macro_error]
+pub fn derive_count_level(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input: syn::DeriveInput = syn::parse_macro_input!(input);
     let name = &input.ident;
-    Generator::gen_impl(name, &app).into()
+    let variants = match &input.data {
+        syn::Data::Enum(data) if !data.variants.is_empty() => &data.variants,
+        _ => abort!(
+            input,
+            "`#[derive(CountLevel)]` only works for enums with variants"
+        ),
+    };
+    let idents: Vec<&Ident> = variants
+        .iter()
+        .map(|v| match v.fields {
+            syn::Fields::Unit => &v.ident,
+            _ => abort!(v, "A level cannot have fields"),
+        })
+        .collect();
+    let indices = 0..idents.len();
+    quote!(
+        impl ::miniclap::CountLevel for #name {
+            const LEVELS: &'static [Self] = &[ #(Self::#idents),* ];
+
+            fn level_index(&self) -> usize {
+                match self {
+                    #(Self::#idents => #indices,)*
+                }
+            }
+        }
+    )
+    .into()
 }
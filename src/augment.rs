@@ -0,0 +1,152 @@
+use crate::{assign, parse_args, App, ArgOsIterator, Error, FlagHandler, OptionHandler, Result};
+use crate::{FlagAssign, ParsedAssign, Switch};
+use std::error::Error as StdError;
+use std::str::FromStr;
+
+/// Flags and options added at runtime to the arguments of a derived struct, such as by plugins.
+/// The derive passes one to the function given by `#[miniclap(augment = "path")]` on each parse.
+///
+/// ```
+/// use miniclap::{AppBuilder, MiniClap, Switch};
+/// use std::cell::Cell;
+///
+/// thread_local! {
+///     static COLOR: Cell<bool> = const { Cell::new(false) };
+/// }
+///
+/// #[derive(MiniClap)]
+/// #[miniclap(augment = "plugins")]
+/// struct Opts {
+///     #[miniclap(short)]
+///     verbose: bool,
+/// }
+///
+/// fn plugins(app: &mut AppBuilder) {
+///     app.flag(Switch::Long("color"), || COLOR.with(|x| x.set(true)));
+/// }
+///
+/// let opts = Opts::try_parse_from(["test", "-v", "--color"]).unwrap();
+/// assert!(opts.verbose);
+/// assert!(COLOR.with(Cell::get));
+/// ```
+#[derive(Default)]
+pub struct AppBuilder {
+    flags: Vec<Added<dyn assign::FlagAssign>>,
+    options: Vec<Added<dyn assign::StringAssign>>,
+}
+
+struct Added<A: ?Sized> {
+    name: String,
+    short: Option<char>,
+    long: Option<String>,
+    assign: Box<A>,
+}
+
+impl<A: ?Sized> Added<A> {
+    fn new(switch: Switch, assign: Box<A>) -> Self {
+        let (short, long) = match switch {
+            Switch::Short(c) => (Some(c), None),
+            Switch::Long(l) => (None, Some(l.to_string())),
+            Switch::Both(c, l) => (Some(c), Some(l.to_string())),
+        };
+        let name = long.clone().unwrap_or_else(|| short.unwrap().to_string());
+        Added {
+            name,
+            short,
+            long,
+            assign,
+        }
+    }
+
+    fn switch(&self) -> Switch<'_> {
+        match (self.short, &self.long) {
+            (Some(c), Some(l)) => Switch::Both(c, l),
+            (None, Some(l)) => Switch::Long(l),
+            (Some(c), None) => Switch::Short(c),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+impl AppBuilder {
+    /// Adds a flag, calling `assign` each time it is given.
+    pub fn flag<F>(&mut self, switch: Switch, assign: F) -> &mut Self
+    where
+        F: FnMut() + 'static,
+    {
+        let assign = Box::new(FlagAssign::new(assign));
+        self.flags.push(Added::new(switch, assign));
+        self
+    }
+
+    /// Adds an option, calling `assign` with each value given once it is parsed as `T`.
+    pub fn option<T, F>(&mut self, switch: Switch, assign: F) -> &mut Self
+    where
+        T: FromStr + 'static,
+        <T as FromStr>::Err: StdError + 'static,
+        F: FnMut(T) + 'static,
+    {
+        let assign = Box::new(ParsedAssign::new(assign));
+        self.options.push(Added::new(switch, assign));
+        self
+    }
+
+    /// Parses `args` with the added arguments after those of `app`. A switch that is already used
+    /// fails the parse, since the derive would have rejected it for a field.
+    #[doc(hidden)]
+    pub fn __parse(&self, args: ArgOsIterator, app: &App) -> Result<()> {
+        let mut flags = app.flags.to_vec();
+        let mut options = app.options.to_vec();
+        for added in &self.flags {
+            check_unused(&added.name, added.switch(), &flags, &options)?;
+            flags.push(FlagHandler {
+                name: &added.name,
+                switch: added.switch(),
+                is_exclusive: false,
+                assign: &*added.assign,
+            });
+        }
+        for added in &self.options {
+            check_unused(&added.name, added.switch(), &flags, &options)?;
+            options.push(OptionHandler {
+                name: &added.name,
+                switch: added.switch(),
+                is_raw: false,
+                is_optional_bool: false,
+                default_missing_value: None,
+                require_equals: false,
+                assign: &*added.assign,
+            });
+        }
+        let app = &App {
+            flags: &flags,
+            options: &options,
+            ..*app
+        };
+        parse_args(args, app)
+    }
+}
+
+/// Fails if the short or long name of `switch` is used by any of the handlers.
+fn check_unused(
+    name: &str,
+    switch: Switch,
+    flags: &[FlagHandler],
+    options: &[OptionHandler],
+) -> Result<()> {
+    let clashes = |used: Switch| match switch {
+        Switch::Short(c) => used == c,
+        Switch::Long(l) => used == l,
+        Switch::Both(c, l) => used == c || used == l,
+    };
+    let used = flags
+        .iter()
+        .map(|h| (h.name, h.switch))
+        .chain(options.iter().map(|h| (h.name, h.switch)));
+    for (other, used) in used {
+        if clashes(used) {
+            return Err(Error::duplicate_switch(name, other));
+        }
+    }
+    Ok(())
+}
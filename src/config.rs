@@ -0,0 +1,107 @@
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Defaults read from a config file given with the option marked `config_file`.
+///
+/// Each line is `key = value`, where the key is the long name of an argument. Whitespace around
+/// the key and value is ignored, as are blank lines, lines starting with `#` or `;`, and
+/// `[section]` headers. A value may be wrapped in double quotes to keep surrounding whitespace.
+/// A later line for the same key replaces an earlier one.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    values: HashMap<String, String>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &OsStr) -> Result<ConfigFile> {
+        let path = Path::new(path);
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            Error::other_with_source(
+                format!("Cannot read config file '{}': {}", path.display(), e),
+                Box::new(e),
+            )
+        })?;
+        ConfigFile::parse_text(&text).map_err(|line| {
+            Error::other(format!(
+                "Invalid line {} in config file '{}' (expected 'key = value')",
+                line,
+                path.display()
+            ))
+        })
+    }
+
+    /// Parses the contents of a config file, or gives the number of the first invalid line.
+    fn parse_text(text: &str) -> std::result::Result<ConfigFile, usize> {
+        let mut values = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(&['#', ';'][..]) {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or(i + 1)?;
+            let key = key.trim();
+            if key.is_empty() {
+                return Err(i + 1);
+            }
+            let value = value.trim();
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(quoted) => quoted,
+                None => value,
+            };
+            values.insert(key.to_string(), value.to_string());
+        }
+        Ok(ConfigFile { values })
+    }
+
+    /// Parses the value for `key` as the value of the argument `name`, or `None` if there is
+    /// none.
+    pub fn parse<T>(&self, name: &str, key: &str) -> Result<Option<T>>
+    where
+        T: FromStr + 'static,
+        <T as FromStr>::Err: StdError + 'static,
+    {
+        let value = match self.values.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value.parse() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(e) => Err(Error::parse_failed::<T>(name, value, Box::new(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_text() {
+        let config = ConfigFile::parse_text(
+            "# comment\n; comment\n[server]\n\nport = 80\nname=\" a b \"\nport=8080\nempty =\n",
+        )
+        .unwrap();
+        assert_eq!(config.parse::<u16>("port", "port").unwrap(), Some(8080));
+        assert_eq!(
+            config.parse::<String>("name", "name").unwrap().as_deref(),
+            Some(" a b ")
+        );
+        assert_eq!(
+            config.parse::<String>("e", "empty").unwrap().as_deref(),
+            Some("")
+        );
+        assert_eq!(config.parse::<String>("x", "missing").unwrap(), None);
+        assert!(config.parse::<u16>("name", "name").is_err());
+
+        assert_eq!(ConfigFile::parse_text("a = 1\nb\n").unwrap_err(), 2);
+        assert_eq!(ConfigFile::parse_text("= 1\n").unwrap_err(), 1);
+    }
+}
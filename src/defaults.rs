@@ -0,0 +1,80 @@
+use crate::{Error, Result};
+use std::cell::RefCell;
+use std::error::Error as StdError;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::str::FromStr;
+
+type Defaults = Rc<dyn Fn(&str) -> Option<String>>;
+
+thread_local! {
+    /// Supplies values for the parse in progress on this thread, if it was given defaults.
+    static SUPPLIED: RefCell<Option<Defaults>> = const { RefCell::new(None) };
+}
+
+/// Runs `parse`, taking the value of any argument it leaves unset from `defaults`.
+pub(crate) fn with_defaults<T>(defaults: Defaults, parse: impl FnOnce() -> T) -> T {
+    let outer = SUPPLIED.with(|supplied| supplied.replace(Some(defaults)));
+    let result = parse();
+    SUPPLIED.with(|supplied| supplied.replace(outer));
+    result
+}
+
+/// Parses the value supplied for the argument `name` by the defaults of the parse in progress,
+/// if there are any and they have one.
+#[doc(hidden)]
+pub fn supplied_default<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr + 'static,
+    <T as FromStr>::Err: StdError + 'static,
+{
+    // Cloned out so that the defaults may themselves parse.
+    let defaults = match SUPPLIED.with(|supplied| supplied.borrow().clone()) {
+        Some(defaults) => defaults,
+        None => return Ok(None),
+    };
+    match defaults(name) {
+        Some(value) => match value.parse() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(e) => Err(Error::parse_failed::<T>(name, &value, Box::new(e))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The value of a required argument when an exclusive flag was given, which is the value
+/// collected if there was one and otherwise the type's default. A type without a default needs a
+/// `default_value` or an `Option` to be used alongside an exclusive flag.
+#[doc(hidden)]
+pub fn exclusive_default<T: Default>(value: Option<T>) -> T {
+    value.unwrap_or_default()
+}
+
+/// Finds the default of `T`, if it has one, for a required argument of a flattened struct when an
+/// exclusive flag of an outer struct was given. The derive calls `default_of` on a reference to
+/// it with both traits in scope, and [`HasDefault`] is preferred since it takes that reference
+/// itself.
+#[doc(hidden)]
+pub struct DefaultOf<T>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait HasDefault<T> {
+    fn default_of(&self) -> Option<T>;
+}
+
+impl<T: Default> HasDefault<T> for DefaultOf<T> {
+    fn default_of(&self) -> Option<T> {
+        Some(T::default())
+    }
+}
+
+#[doc(hidden)]
+pub trait NoDefault<T> {
+    fn default_of(&self) -> Option<T>;
+}
+
+impl<T> NoDefault<T> for &DefaultOf<T> {
+    fn default_of(&self) -> Option<T> {
+        None
+    }
+}
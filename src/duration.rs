@@ -0,0 +1,130 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Parses a `Duration` from one or more numbers each followed by a unit, such as `1500ms`, `2s`
+/// or `1h30m`. The units are `ns`, `us`, `ms`, `s`, `m`, `h` and `d`, and each number may have a
+/// fractional part, as in `1.5s`.
+#[doc(hidden)]
+pub struct DurationArg(pub Duration);
+
+/// The error for a value that is not a valid duration.
+#[derive(Debug)]
+pub struct ParseDurationError {
+    value: String,
+}
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid duration '{}', expected a number and unit such as '1500ms' or '2s'",
+            self.value
+        )
+    }
+}
+
+impl StdError for ParseDurationError {}
+
+/// Displays the duration in a form that parses back to it, as in `1s500ms`.
+impl fmt::Display for DurationArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (secs, nanos) = (self.0.as_secs(), self.0.subsec_nanos());
+        if secs > 0 || nanos == 0 {
+            write!(f, "{}s", secs)?;
+        }
+        match nanos {
+            0 => Ok(()),
+            n if n % 1_000_000 == 0 => write!(f, "{}ms", n / 1_000_000),
+            n if n % 1_000 == 0 => write!(f, "{}us", n / 1_000),
+            n => write!(f, "{}ns", n),
+        }
+    }
+}
+
+fn unit_nanos(unit: &str) -> Option<f64> {
+    Some(match unit {
+        "ns" => 1.0,
+        "us" => 1e3,
+        "ms" => 1e6,
+        "s" => 1e9,
+        "m" => 60e9,
+        "h" => 3600e9,
+        "d" => 86400e9,
+        _ => return None,
+    })
+}
+
+impl FromStr for DurationArg {
+    type Err = ParseDurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseDurationError {
+            value: s.to_string(),
+        };
+        let mut rest = s;
+        let mut total = Duration::new(0, 0);
+        if rest.is_empty() {
+            return Err(err());
+        }
+        while !rest.is_empty() {
+            let number_len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let (number, tail) = rest.split_at(number_len);
+            let unit_len = tail
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(tail.len());
+            let (unit, tail) = tail.split_at(unit_len);
+            let number: f64 = number.parse().map_err(|_| err())?;
+            let nanos = number * unit_nanos(unit).ok_or_else(err)?;
+            total = total
+                .checked_add(Duration::from_nanos(nanos.round() as u64))
+                .ok_or_else(err)?;
+            rest = tail;
+        }
+        Ok(DurationArg(total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<Duration> {
+        s.parse::<DurationArg>().ok().map(|x| x.0)
+    }
+
+    #[test]
+    fn spellings() {
+        assert_eq!(parse("1500ms"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse("250us"), Some(Duration::from_micros(250)));
+        assert_eq!(parse("0ns"), Some(Duration::new(0, 0)));
+    }
+
+    #[test]
+    fn invalid() {
+        for s in ["", "5", "s", "1.2.3s", "10 s", "-1s", "3w", "1s2"] {
+            assert_eq!(parse(s), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for (s, shown) in [
+            ("0s", "0s"),
+            ("1.5s", "1s500ms"),
+            ("250us", "250us"),
+            ("2m7ns", "120s7ns"),
+        ] {
+            let duration = parse(s).unwrap();
+            assert_eq!(DurationArg(duration).to_string(), shown);
+            assert_eq!(parse(shown), Some(duration));
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{Error, Result};
+use std::error::Error as StdError;
+use std::ffi::{OsStr, OsString};
+use std::str::FromStr;
+
+/// Reads the environment variable `name` as a boolean, or `None` if it is not set.
+///
+/// Follows the `CLICOLOR` convention: empty, `0`, `false` and `no` (in any case) are false and
+/// anything else is true.
+pub fn env_bool<K: AsRef<OsStr>>(name: K) -> Option<bool> {
+    std::env::var_os(name).map(|value| is_truthy(&value))
+}
+
+/// Parses the environment variable `var` as the value of the argument `name`, or `None` if it is
+/// not set.
+#[doc(hidden)]
+pub fn parse_env<T>(name: &str, var: &str) -> Result<Option<T>>
+where
+    T: FromStr + 'static,
+    <T as FromStr>::Err: StdError + 'static,
+{
+    let value = match std::env::var_os(var) {
+        Some(value) => value.into_string().map_err(|_| Error::invalid_utf8())?,
+        None => return Ok(None),
+    };
+    match value.parse() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(e) => Err(Error::parse_failed::<T>(name, &value, Box::new(e))),
+    }
+}
+
+fn is_truthy(value: &OsString) -> bool {
+    let value = value.to_str().map(str::to_ascii_lowercase);
+    !matches!(value.as_deref(), Some("" | "0" | "false" | "no"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conventional_values() {
+        for value in ["", "0", "false", "FALSE", "no", "No"] {
+            assert!(!is_truthy(&OsString::from(value)), "{:?}", value);
+        }
+        for value in ["1", "true", "yes", "on", "always", "00", " "] {
+            assert!(is_truthy(&OsString::from(value)), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn unset() {
+        assert_eq!(env_bool("MINICLAP_TEST_UNSET_VARIABLE"), None);
+    }
+}
@@ -1,17 +1,50 @@
 use crate::Switch;
+use std::any::TypeId;
 use std::error::Error as StdError;
+use std::io::Write;
+use std::process::{ExitCode, Termination};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The process exit code for an error of the given kind, as used by `Error::exit`.
+///
+/// Following the usual shell convention, errors in how the program was invoked, including values
+/// that fail to parse, exit with 2. Other errors, such as from validation, exit with 1.
+pub fn exit_code_for(kind: &ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::ParseFailed
+        | ErrorKind::UnknownSwitch
+        | ErrorKind::UnknownSubcommand
+        | ErrorKind::TooManyPositional
+        | ErrorKind::MissingRequiredArgument
+        | ErrorKind::MissingValue
+        | ErrorKind::UnexpectedValue
+        | ErrorKind::InvalidUtf8
+        | ErrorKind::ArgumentConflict
+        | ErrorKind::MissingRequirement
+        | ErrorKind::OutOfRange => 2,
+        ErrorKind::Validation | ErrorKind::Other => 1,
+    }
+}
+
 #[derive(Debug)]
 pub enum ErrorKind {
     ParseFailed,
     UnknownSwitch,
+    UnknownSubcommand,
     TooManyPositional,
     MissingRequiredArgument,
     MissingValue,
     UnexpectedValue,
     InvalidUtf8,
+    ArgumentConflict,
+    /// An argument was given without another that it requires.
+    MissingRequirement,
+    /// A value parsed but is outside the argument's `range`.
+    OutOfRange,
+    /// The parsed value as a whole failed a check, such as a `validate` function.
+    Validation,
+    /// Anything else, such as an internal error.
     Other,
 }
 
@@ -20,35 +53,156 @@ pub struct Error {
     pub message: String,
     pub kind: ErrorKind,
     pub source: Option<Box<dyn StdError + 'static>>,
+    /// A similar name that was probably meant, such as for a misspelled switch.
+    pub suggestion: Option<String>,
+}
+
+/// Writes the error to stderr and returns its exit code, as `Error::exit` does by default.
+fn report(e: &Error) -> i32 {
+    eprintln!("error: {}", e);
+    exit_code_for(&e.kind)
+}
+
+/// What `main` can return to report an error the way `Error::exit` does, with its message on
+/// stderr and the exit code for its kind.
+///
+/// ```no_run
+/// use miniclap::MiniClap;
+///
+/// #[derive(MiniClap)]
+/// struct Opts {
+///     #[miniclap(long)]
+///     count: u32,
+/// }
+///
+/// fn run() -> miniclap::Result<()> {
+///     let opts = Opts::try_parse()?;
+///     println!("{}", opts.count);
+///     Ok(())
+/// }
+///
+/// fn main() -> miniclap::ExitResult {
+///     run().into()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ExitResult(pub Result<()>);
+
+impl From<Result<()>> for ExitResult {
+    fn from(result: Result<()>) -> Self {
+        ExitResult(result)
+    }
+}
+
+impl From<Error> for ExitResult {
+    fn from(e: Error) -> Self {
+        ExitResult(Err(e))
+    }
+}
+
+impl Termination for ExitResult {
+    fn report(self) -> ExitCode {
+        match self.0 {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                let _ = std::io::stdout().flush();
+                // Exit codes are 0-255 on all platforms.
+                ExitCode::from(report(&e) as u8)
+            }
+        }
+    }
 }
 
 impl Error {
     pub fn exit(&self) -> ! {
-        eprintln!("error: {}", self.message);
-        std::process::exit(1)
+        self.exit_with(report)
     }
 
-    pub(crate) fn parse_failed(name: &str, value: &str, err: Box<dyn StdError>) -> Error {
+    /// Like `exit`, but `report` writes the error out and returns the exit code.
+    pub fn exit_with<F: FnOnce(&Error) -> i32>(&self, report: F) -> ! {
+        // Make sure anything already written reaches its destination before exiting.
+        let _ = std::io::stdout().flush();
+        let code = report(self);
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        std::process::exit(code)
+    }
+
+    /// Describes the error for a value of type `T`, in plainer words for types whose own errors
+    /// don't say what was expected.
+    pub(crate) fn parse_failed<T: 'static>(
+        name: &str,
+        value: &str,
+        err: Box<dyn StdError>,
+    ) -> Error {
+        let message = match network_type::<T>() {
+            Some((kind, example)) => format!(
+                "Invalid {} '{}' for argument '{}' (expected something like {})",
+                kind, value, name, example
+            ),
+            None => format!("Argument '{}' cannot parse '{}': {}", name, value, err),
+        };
         Error {
-            message: format!("Argument '{}' cannot parse '{}': {}", name, value, err),
+            message,
             kind: ErrorKind::ParseFailed,
             source: Some(err),
+            suggestion: None,
         }
     }
 
+    pub(crate) fn with_suggestion(mut self, suggestion: Option<String>) -> Error {
+        self.suggestion = suggestion;
+        self
+    }
+
     pub(crate) fn unknown_switch(switch: Switch) -> Error {
         Error {
             message: format!("Did not recognize argument '{}'", switch),
             kind: ErrorKind::UnknownSwitch,
             source: None,
+            suggestion: None,
+        }
+    }
+
+    /// For an argument such as `-=5` or `--=5`, with a value but no switch name before the `=`.
+    pub(crate) fn missing_switch_name(token: &str) -> Error {
+        Error {
+            message: format!("Argument '{}' has no switch name before the '='", token),
+            kind: ErrorKind::UnknownSwitch,
+            source: None,
+            suggestion: None,
         }
     }
 
-    pub(crate) fn too_many_positional(arg: &str) -> Error {
+    #[doc(hidden)]
+    pub fn unknown_subcommand(name: &str) -> Error {
         Error {
-            message: format!("Too many positional arguments, starting with '{}'", arg),
+            message: format!("Did not recognize subcommand '{}'", name),
+            kind: ErrorKind::UnknownSubcommand,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn missing_subcommand() -> Error {
+        Error {
+            message: "Missing subcommand".into(),
+            kind: ErrorKind::MissingRequiredArgument,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    pub(crate) fn too_many_positional(arg: &str, expected: usize) -> Error {
+        Error {
+            message: format!(
+                "Too many positional arguments, starting with '{}' (expected at most {})",
+                arg, expected
+            ),
             kind: ErrorKind::TooManyPositional,
             source: None,
+            suggestion: None,
         }
     }
 
@@ -58,6 +212,73 @@ impl Error {
             message: format!("Missing required argument '{}'", arg_name),
             kind: ErrorKind::MissingRequiredArgument,
             source: None,
+            suggestion: None,
+        }
+    }
+
+    /// For an array given fewer values than it holds. It is never given more, as the rest go to
+    /// the next positional argument.
+    #[doc(hidden)]
+    pub fn missing_array_values(arg_name: &str, expected: usize, given: usize) -> Error {
+        Error {
+            message: format!(
+                "Argument '{}' expects {} values, got {}",
+                arg_name, expected, given
+            ),
+            kind: ErrorKind::MissingValue,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn missing_required_if_eq(arg_name: &str, other: &str, value: &str) -> Error {
+        Error {
+            message: format!(
+                "Missing required argument '{}' (required when '{}' is '{}')",
+                arg_name, other, value
+            ),
+            kind: ErrorKind::MissingRequiredArgument,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn missing_requirement(arg_name: &str, required: &str) -> Error {
+        Error {
+            message: format!("Argument '{}' requires '{}'", arg_name, required),
+            kind: ErrorKind::MissingRequirement,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn group_conflict(group: &str, members: &[&str]) -> Error {
+        Error {
+            message: format!(
+                "Only one of {} may be given (group '{}')",
+                quoted_list(members),
+                group
+            ),
+            kind: ErrorKind::ArgumentConflict,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn missing_group(group: &str, members: &[&str]) -> Error {
+        Error {
+            message: format!(
+                "One of {} is required (group '{}')",
+                quoted_list(members),
+                group
+            ),
+            kind: ErrorKind::MissingRequiredArgument,
+            source: None,
+            suggestion: None,
         }
     }
 
@@ -66,6 +287,23 @@ impl Error {
             message: format!("Missing value for '{}'", switch),
             kind: ErrorKind::MissingValue,
             source: None,
+            suggestion: None,
+        }
+    }
+
+    pub(crate) fn detached_value(switch: Switch) -> Error {
+        let example = match switch {
+            Switch::Short(c) => format!("-{}value", c),
+            Switch::Long(l) | Switch::Both(_, l) => format!("--{}=value", l),
+        };
+        Error {
+            message: format!(
+                "Missing value for '{}', which must be attached as in '{}'",
+                switch, example
+            ),
+            kind: ErrorKind::MissingValue,
+            source: None,
+            suggestion: None,
         }
     }
 
@@ -74,6 +312,50 @@ impl Error {
             message: format!("Flag '{}' cannot take a value", switch),
             kind: ErrorKind::UnexpectedValue,
             source: None,
+            suggestion: None,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn out_of_range<T: std::fmt::Display>(name: &str, value: &T, range: &str) -> Error {
+        Error {
+            message: format!(
+                "Argument '{}' must be in the range {}, got '{}'",
+                name, range, value
+            ),
+            kind: ErrorKind::OutOfRange,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    pub(crate) fn exclusive(switch: Switch) -> Error {
+        Error {
+            message: format!("'{}' cannot be used with other arguments", switch),
+            kind: ErrorKind::ArgumentConflict,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    pub(crate) fn duplicate_switch(name: &str, used_by: &str) -> Error {
+        Error {
+            message: format!(
+                "Added argument '{}' uses a switch of argument '{}'",
+                name, used_by
+            ),
+            kind: ErrorKind::Other,
+            source: None,
+            suggestion: None,
+        }
+    }
+
+    pub(crate) fn too_many_args(max: usize) -> Error {
+        Error {
+            message: format!("Too many arguments (expected at most {})", max),
+            kind: ErrorKind::Other,
+            source: None,
+            suggestion: None,
         }
     }
 
@@ -82,19 +364,66 @@ impl Error {
             message: "Invalid UTF-8 was detected in one or more arguments".into(),
             kind: ErrorKind::InvalidUtf8,
             source: None,
+            suggestion: None,
+        }
+    }
+
+    /// An error for a `validate` function to return when the parsed value as a whole is invalid.
+    pub fn validation<I: Into<String>>(message: I) -> Error {
+        Error {
+            message: message.into(),
+            kind: ErrorKind::Validation,
+            source: None,
+            suggestion: None,
         }
     }
 
-    #[doc(hidden)]
     pub fn other<I: Into<String>>(message: I) -> Error {
         Error {
             message: message.into(),
             kind: ErrorKind::Other,
             source: None,
+            suggestion: None,
+        }
+    }
+
+    pub fn other_with_source<I: Into<String>>(message: I, source: Box<dyn StdError>) -> Error {
+        Error {
+            message: message.into(),
+            kind: ErrorKind::Other,
+            source: Some(source),
+            suggestion: None,
         }
     }
 }
 
+/// Formats `names` as `'a', 'b', 'c'`.
+fn quoted_list(names: &[&str]) -> String {
+    let quoted: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    quoted.join(", ")
+}
+
+/// Names the `std::net` address type `T`, with an example of its syntax.
+fn network_type<T: 'static>() -> Option<(&'static str, &'static str)> {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+    let id = TypeId::of::<T>();
+    Some(if id == TypeId::of::<IpAddr>() {
+        ("IP address", "127.0.0.1 or ::1")
+    } else if id == TypeId::of::<Ipv4Addr>() {
+        ("IPv4 address", "127.0.0.1")
+    } else if id == TypeId::of::<Ipv6Addr>() {
+        ("IPv6 address", "::1")
+    } else if id == TypeId::of::<SocketAddr>() {
+        ("socket address", "127.0.0.1:8080 or [::1]:8080")
+    } else if id == TypeId::of::<SocketAddrV4>() {
+        ("IPv4 socket address", "127.0.0.1:8080")
+    } else if id == TypeId::of::<SocketAddrV6>() {
+        ("IPv6 socket address", "[::1]:8080")
+    } else {
+        return None;
+    })
+}
+
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         self.source.as_ref().map(|x| x.as_ref())
@@ -103,6 +432,78 @@ impl StdError for Error {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean '{}'?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        for kind in [
+            ErrorKind::ParseFailed,
+            ErrorKind::UnknownSwitch,
+            ErrorKind::UnknownSubcommand,
+            ErrorKind::TooManyPositional,
+            ErrorKind::MissingRequiredArgument,
+            ErrorKind::MissingValue,
+            ErrorKind::UnexpectedValue,
+            ErrorKind::InvalidUtf8,
+            ErrorKind::ArgumentConflict,
+            ErrorKind::MissingRequirement,
+            ErrorKind::OutOfRange,
+        ] {
+            assert_eq!(exit_code_for(&kind), 2, "{:?}", kind);
+        }
+        for kind in [ErrorKind::Validation, ErrorKind::Other] {
+            assert_eq!(exit_code_for(&kind), 1, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn validation_kinds() {
+        let kind = |err: Error| err.kind;
+        assert!(matches!(
+            kind(Error::out_of_range("n", &0, "1..=9")),
+            ErrorKind::OutOfRange
+        ));
+        assert!(matches!(
+            kind(Error::missing_requirement("tls", "key")),
+            ErrorKind::MissingRequirement
+        ));
+        assert!(matches!(
+            kind(Error::missing_required_if_eq("port", "mode", "server")),
+            ErrorKind::MissingRequiredArgument
+        ));
+        assert!(matches!(
+            kind(Error::group_conflict("format", &["json", "yaml"])),
+            ErrorKind::ArgumentConflict
+        ));
+        assert!(matches!(
+            kind(Error::missing_group("format", &["json", "yaml"])),
+            ErrorKind::MissingRequiredArgument
+        ));
+        assert!(matches!(
+            kind(Error::exclusive(Switch::Long("list"))),
+            ErrorKind::ArgumentConflict
+        ));
+        assert!(matches!(
+            kind(Error::validation("--min must not exceed --max")),
+            ErrorKind::Validation
+        ));
+    }
+
+    #[test]
+    fn other_with_source() {
+        let cause = "x".parse::<i32>().unwrap_err();
+        let err = Error::other_with_source("Invalid config", Box::new(cause.clone()));
+        assert_eq!(err.to_string(), "Invalid config");
+        assert_eq!(err.source().map(|e| e.to_string()), Some(cause.to_string()));
     }
 }
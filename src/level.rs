@@ -0,0 +1,16 @@
+/// A type that a `count` flag steps up through, one level for each time it is given, such as a
+/// log level. Derive it for an enum without fields with `#[derive(CountLevel)]`, which takes the
+/// variants in order, the first being the level when the flag is not given.
+pub trait CountLevel: Clone + 'static {
+    const LEVELS: &'static [Self];
+
+    /// The position of this level in `LEVELS`.
+    fn level_index(&self) -> usize;
+}
+
+/// The level after `occurrences` steps, staying at the last one however many more there are.
+#[doc(hidden)]
+pub fn count_level<T: CountLevel>(occurrences: usize) -> T {
+    let last = T::LEVELS.len() - 1;
+    T::LEVELS[occurrences.min(last)].clone()
+}
@@ -1,18 +1,181 @@
-pub use miniclap_derive::MiniClap;
+/// Each switch may only be used by one field:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// struct Opts {
+///     #[miniclap(short)]
+///     verbose: bool,
+///     #[miniclap(long, short = 'v')]
+///     version: bool,
+/// }
+/// ```
+///
+/// Nor may a field use a short switch reserved with `reserved_shorts`:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// #[miniclap(reserved_shorts = "hV")]
+/// struct Opts {
+///     #[miniclap(short)]
+///     host: String,
+/// }
+/// ```
+///
+/// Only a single value can be made from whether a switch was given:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// struct Opts {
+///     #[miniclap(long, takes_value = false)]
+///     color: Option<String>,
+/// }
+/// ```
+///
+/// Each subcommand name or alias may only select one variant:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// enum Command {
+///     #[miniclap(alias = "b")]
+///     Build,
+///     #[miniclap(hidden_alias = "b")]
+///     Bench,
+/// }
+/// ```
+///
+/// Values fill positional arguments in order, so a required one may not follow an optional one:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// struct Opts {
+///     source: String,
+///     mode: Option<String>,
+///     dest: String,
+/// }
+/// ```
+///
+/// Positional arguments come before a subcommand name, so they must be required, or the name
+/// could be taken for one:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// enum Command {
+///     Build,
+/// }
+///
+/// #[derive(miniclap::MiniClap)]
+/// struct Opts {
+///     project: Option<String>,
+///     #[miniclap(subcommand)]
+///     command: Command,
+/// }
+/// ```
+///
+/// When an exclusive flag is given, required arguments take their type's default, so any without
+/// one needs a `default_value` or an `Option`:
+///
+/// ```compile_fail
+/// #[derive(miniclap::MiniClap)]
+/// struct Opts {
+///     #[miniclap(long, exclusive)]
+///     list: bool,
+///     #[miniclap(long)]
+///     listen: std::net::SocketAddr,
+/// }
+/// ```
+pub use miniclap_derive::{CountLevel, MiniClap};
 use std::error::Error as StdError;
-use std::{cell::RefCell, ffi::OsString, marker::PhantomData, str::FromStr};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::OsString,
+    marker::PhantomData,
+    str::FromStr,
+};
+
+mod augment;
+pub use augment::AppBuilder;
+
+mod level;
+#[doc(hidden)]
+pub use level::count_level;
+pub use level::CountLevel;
+
+mod defaults;
+#[doc(hidden)]
+pub use defaults::{exclusive_default, supplied_default, DefaultOf, HasDefault, NoDefault};
+
+mod duration;
+pub use duration::{DurationArg, ParseDurationError};
+
+mod radix;
+#[doc(hidden)]
+pub use radix::{parse_radix, FromStrRadix};
+
+mod pair;
+#[doc(hidden)]
+pub use pair::parse_pair;
+
+mod config;
+#[doc(hidden)]
+pub use config::ConfigFile;
+
+mod env;
+pub use env::env_bool;
+#[doc(hidden)]
+pub use env::parse_env;
 
 mod error;
-pub use error::{Error, ErrorKind, Result};
+pub use error::{exit_code_for, Error, ErrorKind, ExitResult, Result};
+
+mod matches;
+pub use matches::ArgMatches;
 
 mod parse;
+pub use parse::parse_matches;
+#[doc(hidden)]
+pub use parse::{parse_args, parse_subcommand};
+
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "metrics")]
+pub use metrics::ParseMetrics;
+
+#[cfg(feature = "prompt")]
+mod prompt;
+#[doc(hidden)]
+#[cfg(feature = "prompt")]
+pub use prompt::prompt;
+#[cfg(feature = "prompt")]
+pub use prompt::with_prompt_reader;
+
+mod spec;
+pub use spec::{generate_schema, ArgInfo, ArgKind, SubcommandInfo};
+
+mod split;
+pub use split::split_words;
+
+mod warning;
 #[doc(hidden)]
-pub use parse::parse_args;
+pub use warning::warn;
+pub use warning::ParseResult;
+
+mod writer;
+#[doc(hidden)]
+pub use writer::ArgWriter;
 
 #[doc(hidden)]
 pub type ArgOsIterator<'a> = &'a mut dyn Iterator<Item = OsString>;
 
 pub trait MiniClap: Sized {
+    /// Describes each argument. The derive lists those with switches first, then the positional
+    /// arguments in order and then any subcommand. It leaves out the arguments of flattened
+    /// fields, and implements this only for structs.
+    const SPEC: &'static [ArgInfo] = &[];
+
+    /// Describes each subcommand, in declaration order. The derive implements this only for
+    /// enums.
+    const SUBCOMMANDS: &'static [SubcommandInfo] = &[];
+
     #[inline]
     fn parse_or_exit() -> Self {
         Self::parse_or_exit_from(std::env::args_os())
@@ -24,19 +187,111 @@ pub trait MiniClap: Sized {
         I: IntoIterator<Item = T>,
         T: Into<OsString>,
     {
-        match Self::try_parse_from(args) {
+        match Self::try_parse_from(args).and_then(|x| x.validate().map(|()| x)) {
             Ok(x) => x,
             Err(e) => e.exit(),
         }
     }
 
+    /// Like `parse_or_exit`, but errors are reported by `report`, which returns the exit code.
+    #[inline]
+    fn parse_or_exit_with<F>(report: F) -> Self
+    where
+        F: FnOnce(&Error) -> i32,
+    {
+        Self::parse_or_exit_from_with(std::env::args_os(), report)
+    }
+
+    /// Like `parse_or_exit_from`, but errors are reported by `report`, which returns the exit
+    /// code.
+    #[inline]
+    fn parse_or_exit_from_with<I, T, F>(args: I, report: F) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+        F: FnOnce(&Error) -> i32,
+    {
+        match Self::try_parse_from(args).and_then(|x| x.validate().map(|()| x)) {
+            Ok(x) => x,
+            Err(e) => e.exit_with(report),
+        }
+    }
+
     #[inline]
     fn try_parse() -> Result<Self> {
         Self::try_parse_from(std::env::args_os())
     }
 
+    /// Like `try_parse`, but returns the warnings from parsing instead of printing them, such as
+    /// for deprecated arguments that were given.
+    #[inline]
+    fn try_parse_with_warnings() -> Result<ParseResult<Self>> {
+        Self::try_parse_from_with_warnings(std::env::args_os())
+    }
+
+    /// Like `try_parse_from`, but returns the warnings from parsing instead of printing them.
+    /// With the `metrics` feature, this also counts the work done by the parse.
+    #[inline]
+    fn try_parse_from_with_warnings<I, T>(args: I) -> Result<ParseResult<Self>>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        warning::collect(|| Self::try_parse_from(args))
+    }
+
+    /// Like `try_parse_from`, but any argument that is still unset after the command line, the
+    /// environment and any config file is taken from `defaults`, which is called with the field
+    /// name and whose value is parsed as if it were given. This applies to the fields of
+    /// flattened structs and subcommands too, but not to counts or `raw` and `trailing`
+    /// arguments.
+    #[inline]
+    fn try_parse_from_with_defaults<I, T, D>(args: I, defaults: D) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+        D: Fn(&str) -> Option<String> + 'static,
+    {
+        defaults::with_defaults(std::rc::Rc::new(defaults), || Self::try_parse_from(args))
+    }
+
+    /// Parses `args`, whose first item is the binary name and is ignored, as with
+    /// `std::env::args_os()`.
     #[inline]
     fn try_parse_from<I, T>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let args = &mut args.into_iter().map(|x| x.into());
+        let _bin_name = args.next();
+        Self::__parse_internal(args)
+    }
+
+    /// Like `try_parse_from`, but takes an iterator of `OsString`s by value and parses straight
+    /// from it, without converting each item.
+    #[inline]
+    fn try_parse_from_os<I>(mut args: I) -> Result<Self>
+    where
+        I: Iterator<Item = OsString>,
+    {
+        let _bin_name = args.next();
+        Self::__parse_internal(&mut args)
+    }
+
+    /// Like `try_parse_from`, but for `String`s. Each is moved into an `OsString` without copying,
+    /// and since all of them are valid UTF-8 this never fails with `ErrorKind::InvalidUtf8`.
+    #[inline]
+    fn try_parse_from_strings<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        Self::try_parse_from_os(args.into_iter().map(OsString::from))
+    }
+
+    /// Parses `args` where every item is an argument, i.e. there is no leading binary name.
+    #[inline]
+    fn try_parse_without_bin_name<I, T>(args: I) -> Result<Self>
     where
         I: IntoIterator<Item = T>,
         T: Into<OsString>,
@@ -44,61 +299,145 @@ pub trait MiniClap: Sized {
         Self::__parse_internal(&mut args.into_iter().map(|x| x.into()))
     }
 
+    /// Checks the parsed value as a whole. `parse_or_exit` and `parse_or_exit_from` call this
+    /// after a successful parse and exit on failure just like on a parse error. The derive
+    /// implements it with `#[miniclap(validate = "path")]`. Failures are usually reported with
+    /// `Error::validation`.
+    #[inline]
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Adds flags and options at runtime, such as for plugins, which are parsed along with the
+    /// derived ones. The derive implements it with `#[miniclap(augment = "path")]`.
+    #[inline]
+    fn augment(_app: &mut AppBuilder) {}
+
+    /// Splits `line` into words with `split_words` and parses them. There is no leading binary
+    /// name, which suits REPLs and embedded command lines.
+    #[inline]
+    fn try_parse_str(line: &str) -> Result<Self> {
+        Self::try_parse_without_bin_name(split_words(line)?)
+    }
+
     #[doc(hidden)]
     fn __parse_internal(args: ArgOsIterator) -> Result<Self>;
 }
 
 #[doc(hidden)]
+pub trait Subcommand: MiniClap {
+    fn __parse_subcommand(name: &str, args: ArgOsIterator) -> Result<Self>;
+}
+
+/// Implemented by the derive for structs, so that their arguments can be merged into another
+/// struct's with `#[miniclap(flatten)]`.
+#[doc(hidden)]
+pub trait Flatten: Sized {
+    type Matches;
+
+    /// Appends this struct's handlers to those of `outer` and passes the merged app to `parse`.
+    fn __collect_with(
+        outer: &App,
+        sequence: &Sequence,
+        parse: &mut dyn FnMut(&App) -> Result<()>,
+    ) -> Result<Self::Matches>;
+
+    fn __build(matches: Self::Matches) -> Result<Self>;
+
+    /// Builds the struct as when an exclusive flag of the outer struct was given, without
+    /// checking that its required arguments were given.
+    fn __build_exclusive(matches: Self::Matches) -> Result<Self>;
+}
+
+#[doc(hidden)]
+#[derive(Default)]
 pub struct App<'a> {
     pub flags: &'a [FlagHandler<'a>],
     pub options: &'a [OptionHandler<'a>],
     pub positions: &'a [PositionalHandler<'a>],
+    pub subcommand: Option<SubcommandHandler<'a>>,
+    /// Receives any positional argument that neither a positional nor a subcommand takes, instead
+    /// of failing with too many positional arguments.
+    pub overflow: Option<PositionalHandler<'a>>,
+    /// Receives every argument after `--` instead of the positional arguments, such as the
+    /// arguments for a program being run.
+    pub trailing: Option<PositionalHandler<'a>>,
+    /// An argument starting with an unknown switch is handled whole as a positional argument,
+    /// such as `--foo=1`, or `-xyz` when `-x` is unknown. A value after it is parsed as usual.
+    pub unknown_as_positional: bool,
+    /// An argument that looks like a negative number, such as `-5` or `-1.5`, is a positional
+    /// argument unless its first character after the `-` is a short switch.
+    pub allow_negative_numbers: bool,
+    /// An option given without a value may be followed by a lone `=` and then the value, as in
+    /// `--name = value`.
+    pub allow_space_around_equals: bool,
+    /// A positional argument that nothing takes is dropped with a warning, instead of failing
+    /// with too many positional arguments.
+    pub ignore_extra_positionals: bool,
+    /// A short switch that is both a flag and an option, such as when a flattened struct reuses
+    /// the outer struct's short, is taken as the option, so `-xyz` gives `-x` the value `yz`. By
+    /// default it is taken as the flag, and `y` and `z` as more switches.
+    pub prefer_short_options: bool,
+    /// Parsing fails once more than this many arguments are given, to bound the work done on
+    /// untrusted input. Arguments taken by a raw option or a subcommand count too.
+    pub max_args: Option<usize>,
+    /// If set, each `--` ends a batch of arguments instead of starting trailing mode: this is
+    /// called, and the positional arguments start over from the first.
+    pub batch_end: Option<&'a dyn assign::FlagAssign>,
 }
 
 #[doc(hidden)]
+#[derive(Copy, Clone)]
 pub struct FlagHandler<'a> {
     pub name: &'a str,
     pub switch: Switch<'a>,
+    /// No other argument may be given with this flag.
+    pub is_exclusive: bool,
     pub assign: &'a dyn assign::FlagAssign,
 }
 
 #[doc(hidden)]
+#[derive(Copy, Clone)]
 pub struct OptionHandler<'a> {
     pub name: &'a str,
     pub switch: Switch<'a>,
+    pub is_raw: bool,
+    /// Without an attached value, takes the next argument only if it is `true` or `false`, and
+    /// otherwise uses `true`.
+    pub is_optional_bool: bool,
+    /// Without an attached value, uses this instead of taking the next argument.
+    pub default_missing_value: Option<&'a str>,
+    /// The value must be attached, as in `--opt=value` or `-ovalue`, and is never taken from the
+    /// next argument.
+    pub require_equals: bool,
     pub assign: &'a dyn assign::StringAssign,
 }
 
 #[doc(hidden)]
+#[derive(Copy, Clone)]
 pub struct PositionalHandler<'a> {
     pub name: &'a str,
     pub is_multiple: bool,
+    /// Invalid UTF-8 is replaced with U+FFFD instead of failing to parse.
+    pub is_lossy: bool,
     pub assign: &'a dyn assign::StringAssign,
 }
 
-impl<'a> App<'a> {
-    fn flag_by_short(&self, c: char) -> Option<&FlagHandler<'a>> {
-        self.flags.iter().find(|h| h.switch == c)
-    }
-
-    fn flag_by_long(&self, l: &str) -> Option<&FlagHandler<'a>> {
-        self.flags.iter().find(|h| h.switch == l)
-    }
-
-    fn option_by_short(&self, c: char) -> Option<&OptionHandler<'a>> {
-        self.options.iter().find(|h| h.switch == c)
-    }
-
-    fn option_by_long(&self, l: &str) -> Option<&OptionHandler<'a>> {
-        self.options.iter().find(|h| h.switch == l)
-    }
-}
-
 #[doc(hidden)]
 #[derive(Copy, Clone)]
+pub struct SubcommandHandler<'a> {
+    pub name: &'a str,
+    pub assign: &'a dyn assign::SubcommandAssign,
+}
+
+/// How an argument is named on the command line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Switch<'a> {
+    /// Only a short name, such as `-v`.
     Short(char),
+    /// Only a long name, such as `--verbose`.
     Long(&'a str),
+    /// Both a short and a long name, such as `-v`/`--verbose`.
     Both(char, &'a str),
 }
 
@@ -137,6 +476,15 @@ mod assign {
 
     pub trait StringAssign {
         fn assign(&self, name: &str, value: String) -> crate::Result<()>;
+
+        /// Called instead of `assign` for a value that is not valid UTF-8.
+        fn assign_os(&self, _name: &str, _value: std::ffi::OsString) -> crate::Result<()> {
+            Err(crate::Error::invalid_utf8())
+        }
+    }
+
+    pub trait SubcommandAssign {
+        fn assign(&self, name: &str, args: crate::ArgOsIterator) -> crate::Result<()>;
     }
 }
 
@@ -147,14 +495,30 @@ impl FlagHandler<'_> {
 }
 
 impl OptionHandler<'_> {
-    fn assign(&self, value: String) -> Result<()> {
-        self.assign.assign(self.name, value)
+    fn assign_os(&self, value: OsString) -> Result<()> {
+        match value.into_string() {
+            Ok(value) => self.assign.assign(self.name, value),
+            Err(value) => self.assign.assign_os(self.name, value),
+        }
     }
 }
 
 impl PositionalHandler<'_> {
-    fn assign(&self, value: String) -> Result<()> {
-        self.assign.assign(self.name, value)
+    fn assign_os(&self, value: OsString) -> Result<()> {
+        match value.into_string() {
+            Ok(value) => self.assign.assign(self.name, value),
+            Err(value) if self.is_lossy => {
+                let value = value.to_string_lossy().into_owned();
+                self.assign.assign(self.name, value)
+            }
+            Err(value) => self.assign.assign_os(self.name, value),
+        }
+    }
+}
+
+impl SubcommandHandler<'_> {
+    fn assign(&self, name: &str, args: ArgOsIterator) -> Result<()> {
+        self.assign.assign(name, args)
     }
 }
 
@@ -174,7 +538,7 @@ impl<F> FlagAssign<F> {
 impl<F: FnMut()> assign::FlagAssign for FlagAssign<F> {
     #[inline]
     fn assign(&self) -> Result<()> {
-        (&mut *self.inner.borrow_mut())();
+        (*self.inner.borrow_mut())();
         Ok(())
     }
 }
@@ -185,7 +549,7 @@ pub struct ParsedAssign<T, F> {
     _type: PhantomData<T>,
 }
 
-impl<'a, T, F> ParsedAssign<T, F> {
+impl<T, F> ParsedAssign<T, F> {
     pub fn new(assign: F) -> Self {
         Self {
             assign: RefCell::new(assign),
@@ -196,7 +560,7 @@ impl<'a, T, F> ParsedAssign<T, F> {
 
 impl<T, F> assign::StringAssign for ParsedAssign<T, F>
 where
-    T: FromStr,
+    T: FromStr + 'static,
     <T as FromStr>::Err: StdError + 'static,
     F: FnMut(T),
 {
@@ -204,8 +568,264 @@ where
     fn assign(&self, name: &str, value: String) -> Result<()> {
         let parsed: T = value
             .parse()
-            .map_err(|e| Error::parse_failed(name, &value, Box::new(e)))?;
-        (&mut *self.assign.borrow_mut())(parsed);
+            .map_err(|e| Error::parse_failed::<T>(name, &value, Box::new(e)))?;
+        (*self.assign.borrow_mut())(parsed);
         Ok(())
     }
 }
+
+/// Like `ParsedAssign`, but for types such as `PathBuf` that hold any `OsString`, so values that
+/// are not valid UTF-8 are passed along unchanged rather than failing.
+#[doc(hidden)]
+pub struct OsAssign<T, F> {
+    assign: RefCell<F>,
+    _type: PhantomData<T>,
+}
+
+impl<T, F> OsAssign<T, F> {
+    pub fn new(assign: F) -> Self {
+        Self {
+            assign: RefCell::new(assign),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T, F> assign::StringAssign for OsAssign<T, F>
+where
+    T: From<OsString>,
+    F: FnMut(T),
+{
+    #[inline]
+    fn assign(&self, name: &str, value: String) -> Result<()> {
+        self.assign_os(name, value.into())
+    }
+
+    #[inline]
+    fn assign_os(&self, _name: &str, value: OsString) -> Result<()> {
+        (*self.assign.borrow_mut())(T::from(value));
+        Ok(())
+    }
+}
+
+/// Like `ParsedAssign`, but passes `None` for an empty value instead of parsing it.
+#[doc(hidden)]
+pub struct ClearableAssign<T, F> {
+    assign: RefCell<F>,
+    _type: PhantomData<T>,
+}
+
+impl<T, F> ClearableAssign<T, F> {
+    pub fn new(assign: F) -> Self {
+        Self {
+            assign: RefCell::new(assign),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T, F> assign::StringAssign for ClearableAssign<T, F>
+where
+    T: FromStr + 'static,
+    <T as FromStr>::Err: StdError + 'static,
+    F: FnMut(Option<T>),
+{
+    #[inline]
+    fn assign(&self, name: &str, value: String) -> Result<()> {
+        let parsed: Option<T> = if value.is_empty() {
+            None
+        } else {
+            Some(
+                value
+                    .parse()
+                    .map_err(|e| Error::parse_failed::<T>(name, &value, Box::new(e)))?,
+            )
+        };
+        (*self.assign.borrow_mut())(parsed);
+        Ok(())
+    }
+}
+
+/// Like `ParsedAssign`, but parses integers in the given base.
+#[doc(hidden)]
+pub struct RadixAssign<T, F> {
+    radix: u32,
+    assign: RefCell<F>,
+    _type: PhantomData<T>,
+}
+
+impl<T, F> RadixAssign<T, F> {
+    pub fn new(radix: u32, assign: F) -> Self {
+        Self {
+            radix,
+            assign: RefCell::new(assign),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T, F> assign::StringAssign for RadixAssign<T, F>
+where
+    T: FromStrRadix + 'static,
+    F: FnMut(T),
+{
+    #[inline]
+    fn assign(&self, name: &str, value: String) -> Result<()> {
+        let parsed = parse_radix(name, &value, self.radix)?;
+        (*self.assign.borrow_mut())(parsed);
+        Ok(())
+    }
+}
+
+/// Like `ParsedAssign`, but parses a `key=value` pair.
+#[doc(hidden)]
+pub struct PairAssign<K, V, F> {
+    assign: RefCell<F>,
+    _type: PhantomData<(K, V)>,
+}
+
+impl<K, V, F> PairAssign<K, V, F> {
+    pub fn new(assign: F) -> Self {
+        Self {
+            assign: RefCell::new(assign),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<K, V, F> assign::StringAssign for PairAssign<K, V, F>
+where
+    K: FromStr + 'static,
+    <K as FromStr>::Err: StdError + 'static,
+    V: FromStr + 'static,
+    <V as FromStr>::Err: StdError + 'static,
+    F: FnMut((K, V)),
+{
+    #[inline]
+    fn assign(&self, name: &str, value: String) -> Result<()> {
+        let parsed = parse_pair(name, &value)?;
+        (*self.assign.borrow_mut())(parsed);
+        Ok(())
+    }
+}
+
+/// Keeps every value as given, for apps built by hand that have nowhere else to put them.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct CollectAssign {
+    values: RefCell<Vec<String>>,
+}
+
+impl CollectAssign {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The values received so far, in order.
+    pub fn values(&self) -> impl Iterator<Item = String> {
+        self.values.borrow().clone().into_iter()
+    }
+
+    pub fn into_values(self) -> Vec<String> {
+        self.values.into_inner()
+    }
+}
+
+impl assign::StringAssign for CollectAssign {
+    #[inline]
+    fn assign(&self, _name: &str, value: String) -> Result<()> {
+        self.values.borrow_mut().push(value);
+        Ok(())
+    }
+}
+
+/// Splits each value at any of the delimiter characters and passes every non-empty part on to
+/// `inner`, so that `--ids 1,,2` gives `1` and `2`.
+#[doc(hidden)]
+pub struct DelimitedAssign<A> {
+    delimiters: &'static str,
+    inner: A,
+}
+
+impl<A> DelimitedAssign<A> {
+    pub fn new(delimiters: &'static str, inner: A) -> Self {
+        Self { delimiters, inner }
+    }
+}
+
+impl<A: assign::StringAssign> assign::StringAssign for DelimitedAssign<A> {
+    #[inline]
+    fn assign(&self, name: &str, value: String) -> Result<()> {
+        value
+            .split(|c| self.delimiters.contains(c))
+            .filter(|part| !part.is_empty())
+            .try_for_each(|part| self.inner.assign(name, part.to_string()))
+    }
+}
+
+/// Passes each value through `transform` before passing it on to `inner`, such as to trim it.
+#[doc(hidden)]
+pub struct TransformAssign<A> {
+    transform: fn(String) -> String,
+    inner: A,
+}
+
+impl<A> TransformAssign<A> {
+    pub fn new(transform: fn(String) -> String, inner: A) -> Self {
+        Self { transform, inner }
+    }
+}
+
+impl<A: assign::StringAssign> assign::StringAssign for TransformAssign<A> {
+    #[inline]
+    fn assign(&self, name: &str, value: String) -> Result<()> {
+        self.inner.assign(name, (self.transform)(value))
+    }
+}
+
+#[doc(hidden)]
+pub struct SubcommandAssign<T, F> {
+    assign: RefCell<F>,
+    _type: PhantomData<T>,
+}
+
+impl<T, F> SubcommandAssign<T, F> {
+    pub fn new(assign: F) -> Self {
+        Self {
+            assign: RefCell::new(assign),
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T, F> assign::SubcommandAssign for SubcommandAssign<T, F>
+where
+    T: Subcommand,
+    F: FnMut(T),
+{
+    #[inline]
+    fn assign(&self, name: &str, args: ArgOsIterator) -> Result<()> {
+        let parsed = T::__parse_subcommand(name, args)?;
+        (*self.assign.borrow_mut())(parsed);
+        Ok(())
+    }
+}
+
+/// Numbers each assignment so the order in which arguments appeared can be recovered.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Sequence {
+    last: Cell<usize>,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn next(&self) -> usize {
+        self.last.set(self.last.get() + 1);
+        self.last.get()
+    }
+}
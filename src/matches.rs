@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+/// The arguments given in a parse, looked up by handler name. Returned by `parse_matches` for
+/// apps whose arguments are not known until runtime.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArgMatches {
+    values: HashMap<String, Vec<String>>,
+    occurrences: HashMap<String, usize>,
+}
+
+impl ArgMatches {
+    pub(crate) fn record(&mut self, name: &str, value: Option<&str>) {
+        *self.occurrences.entry(name.to_string()).or_insert(0) += 1;
+        if let Some(value) = value {
+            self.values
+                .entry(name.to_string())
+                .or_default()
+                .push(value.to_string());
+        }
+    }
+
+    /// The last value given for the argument `name`, if any.
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        self.values_of(name).last().map(String::as_str)
+    }
+
+    /// Every value given for the argument `name`, in order.
+    pub fn values_of(&self, name: &str) -> &[String] {
+        self.values.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// How many times the argument `name` was given.
+    pub fn occurrences_of(&self, name: &str) -> usize {
+        self.occurrences.get(name).copied().unwrap_or(0)
+    }
+}
@@ -0,0 +1,36 @@
+use std::cell::Cell;
+
+thread_local! {
+    /// The counts of the parse in progress on this thread, if they are being collected.
+    static COLLECTED: Cell<Option<ParseMetrics>> = const { Cell::new(None) };
+}
+
+/// Counts of the work done by a parse, for catching performance regressions in tests. Returned
+/// in `ParseResult::metrics` with the `metrics` feature.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseMetrics {
+    /// The number of times a switch was looked up among the flags or options.
+    pub lookups: usize,
+    /// The number of handlers compared against a switch over all of the lookups. A lookup in a
+    /// map counts as comparing only the handler found, if any.
+    pub handlers_scanned: usize,
+}
+
+/// Runs `parse`, counting the work done during it, including by the parses of subcommands.
+pub(crate) fn collect<T>(parse: impl FnOnce() -> T) -> (T, ParseMetrics) {
+    let outer = COLLECTED.with(|collected| collected.replace(Some(ParseMetrics::default())));
+    let value = parse();
+    let metrics = COLLECTED.with(|collected| collected.replace(outer));
+    (value, metrics.unwrap_or_default())
+}
+
+/// Counts a lookup that compared `scanned` handlers, if a parse is being counted.
+pub(crate) fn count_lookup(scanned: usize) {
+    COLLECTED.with(|collected| {
+        if let Some(mut metrics) = collected.get() {
+            metrics.lookups += 1;
+            metrics.handlers_scanned += scanned;
+            collected.set(Some(metrics));
+        }
+    });
+}
@@ -0,0 +1,57 @@
+use crate::{Error, Result};
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+
+/// The error for a key-value pair without an `=`.
+#[derive(Debug)]
+struct MissingSeparator;
+
+impl fmt::Display for MissingSeparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected a key and value such as 'key=value'")
+    }
+}
+
+impl StdError for MissingSeparator {}
+
+/// Parses `value` as a key-value pair for the argument `name`, split at the first `=` so that
+/// the value may contain more of them.
+#[doc(hidden)]
+pub fn parse_pair<K, V>(name: &str, value: &str) -> Result<(K, V)>
+where
+    K: FromStr + 'static,
+    <K as FromStr>::Err: StdError + 'static,
+    V: FromStr + 'static,
+    <V as FromStr>::Err: StdError + 'static,
+{
+    let (key, val) = value
+        .split_once('=')
+        .ok_or_else(|| Error::parse_failed::<(K, V)>(name, value, Box::new(MissingSeparator)))?;
+    let key = key
+        .parse()
+        .map_err(|e| Error::parse_failed::<K>(name, key, Box::new(e)))?;
+    let val = val
+        .parse()
+        .map_err(|e| Error::parse_failed::<V>(name, val, Box::new(e)))?;
+    Ok((key, val))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_at_first_equals() {
+        let (key, value): (String, String) = parse_pair("x", "a=b=c").unwrap();
+        assert_eq!((key.as_str(), value.as_str()), ("a", "b=c"));
+        let (key, value): (String, i32) = parse_pair("x", "=-1").unwrap();
+        assert_eq!((key.as_str(), value), ("", -1));
+        let err = parse_pair::<String, i32>("x", "a").unwrap_err();
+        assert_eq!(
+            err.message,
+            "Argument 'x' cannot parse 'a': expected a key and value such as 'key=value'"
+        );
+        assert!(parse_pair::<String, i32>("x", "a=b").is_err());
+    }
+}
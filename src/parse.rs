@@ -1,11 +1,112 @@
-use crate::{App, ArgOsIterator, Error, FlagHandler, OptionHandler, Result, Switch};
+use crate::{
+    env_bool, App, ArgMatches, ArgOsIterator, Error, FlagHandler, OptionHandler, PositionalHandler,
+    Result, Subcommand, Switch,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::OsString;
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+/// Setting this environment variable to a true value (see `env_bool`) logs how each argument is parsed to stderr.
+const DEBUG_ENV: &str = "MINICLAP_DEBUG";
+
+/// Whether `DEBUG_ENV` is set. It is read by the first parse only, so that parsing a subcommand
+/// or parsing again does not read the environment each time.
+fn debug_enabled() -> bool {
+    static READ: Once = Once::new();
+    static DEBUG: AtomicBool = AtomicBool::new(false);
+    READ.call_once(|| DEBUG.store(env_bool(DEBUG_ENV).unwrap_or(false), Ordering::Relaxed));
+    DEBUG.load(Ordering::Relaxed)
+}
+
+/// Apps with more flags and options than this look switches up in maps rather than scanning.
+const INDEX_THRESHOLD: usize = 16;
+
+/// The position of the first handler for each switch.
+#[derive(Default)]
+struct SwitchIndex<'a> {
+    short: HashMap<char, usize>,
+    long: HashMap<&'a str, usize>,
+}
+
+impl<'a> SwitchIndex<'a> {
+    fn new(switches: impl Iterator<Item = Switch<'a>>) -> Self {
+        let mut index = SwitchIndex::default();
+        for (i, switch) in switches.enumerate() {
+            let (short, long) = match switch {
+                Switch::Short(c) => (Some(c), None),
+                Switch::Long(l) => (None, Some(l)),
+                Switch::Both(c, l) => (Some(c), Some(l)),
+            };
+            if let Some(c) = short {
+                index.short.entry(c).or_insert(i);
+            }
+            if let Some(l) = long {
+                index.long.entry(l).or_insert(i);
+            }
+        }
+        index
+    }
+}
+
+struct Index<'a> {
+    flags: SwitchIndex<'a>,
+    options: SwitchIndex<'a>,
+}
+
+impl<'a> Index<'a> {
+    fn new(app: &App<'a>) -> Self {
+        Index {
+            flags: SwitchIndex::new(app.flags.iter().map(|h| h.switch)),
+            options: SwitchIndex::new(app.options.iter().map(|h| h.switch)),
+        }
+    }
+}
+
+/// Passes on at most `remaining` arguments, noting whether there were more.
+struct Budget<'a> {
+    args: ArgOsIterator<'a>,
+    remaining: usize,
+    exceeded: bool,
+}
+
+impl Iterator for Budget<'_> {
+    type Item = OsString;
+
+    fn next(&mut self) -> Option<OsString> {
+        if self.exceeded {
+            return None;
+        }
+        let arg = self.args.next()?;
+        if self.remaining == 0 {
+            self.exceeded = true;
+            return None;
+        }
+        self.remaining -= 1;
+        Some(arg)
+    }
+}
 
 struct Parser<'a> {
     args: ArgOsIterator<'a>,
     app: &'a App<'a>,
     num_args: usize,
+    /// How many arguments have been taken from `args`, including values.
+    num_taken: usize,
     is_trailing: bool,
+    /// An argument that was looked at but not consumed.
+    peeked: Option<OsString>,
+    debug: bool,
+    /// Only built for large apps, which would otherwise scan many handlers per switch.
+    index: Option<Index<'a>>,
+    /// The exclusive flag that was given, if any.
+    exclusive: Cell<Option<Switch<'a>>>,
+    /// Whether any argument other than an exclusive flag was given.
+    others_given: Cell<bool>,
+    /// Only recorded for `parse_matches`.
+    matches: Option<RefCell<ArgMatches>>,
 }
 
 impl<'a> Parser<'a> {
@@ -14,65 +115,237 @@ impl<'a> Parser<'a> {
             args,
             app,
             num_args: 0,
+            num_taken: 0,
             is_trailing: false,
+            peeked: None,
+            debug: debug_enabled(),
+            index: if app.flags.len() + app.options.len() > INDEX_THRESHOLD {
+                Some(Index::new(app))
+            } else {
+                None
+            },
+            exclusive: Cell::new(None),
+            others_given: Cell::new(false),
+            matches: None,
         }
     }
 
-    fn next_value(&mut self, switch: Switch) -> Result<String> {
-        match self.args.next().map(OsString::into_string) {
-            Some(Ok(value)) => Ok(value),
-            Some(Err(_)) => Err(Error::invalid_utf8()),
-            None => Err(Error::missing_value(switch)),
+    fn record(&self, name: &str, value: Option<&str>) {
+        if let Some(matches) = &self.matches {
+            matches.borrow_mut().record(name, value);
         }
     }
 
-    fn parse_long(&mut self, arg: &str) -> Result<()> {
-        // Split at '=' if it exists.
-        let (arg, opt_value) = match arg.find('=') {
-            Some(i) => {
-                let (x, y) = arg.split_at(i);
-                (x, Some(y[1..].to_string()))
-            }
-            None => (arg, None),
+    /// Finds the first handler that `matches`, or uses `indexed` if the switch was looked up in
+    /// the index instead. With the `metrics` feature, the lookup is counted.
+    fn lookup<H>(
+        &self,
+        handlers: &'a [H],
+        indexed: Option<Option<&usize>>,
+        matches: impl Fn(&H) -> bool,
+    ) -> Option<&'a H> {
+        let position = match indexed {
+            Some(position) => position.copied(),
+            None => handlers.iter().position(matches),
         };
-        match (
-            self.app.flag_by_long(arg),
-            self.app.option_by_long(arg),
-            opt_value,
-        ) {
-            (Some(h), _, None) => h.assign(),
-            (Some(_), _, Some(_)) => Err(Error::unexpected_value(Switch::Long(arg))),
-            (_, Some(h), Some(value)) => h.assign(value),
-            (_, Some(h), None) => h.assign(self.next_value(Switch::Long(arg))?),
-            _ => Err(Error::unknown_switch(Switch::Long(arg))),
+        #[cfg(feature = "metrics")]
+        crate::metrics::count_lookup(match indexed {
+            Some(_) => usize::from(position.is_some()),
+            None => position.map_or(handlers.len(), |i| i + 1),
+        });
+        position.map(|i| &handlers[i])
+    }
+
+    fn flag_by_short(&self, c: char) -> Option<&'a FlagHandler<'a>> {
+        let indexed = self.index.as_ref().map(|x| x.flags.short.get(&c));
+        self.lookup(self.app.flags, indexed, |h| h.switch == c)
+    }
+
+    fn flag_by_long(&self, l: &str) -> Option<&'a FlagHandler<'a>> {
+        let indexed = self.index.as_ref().map(|x| x.flags.long.get(l));
+        self.lookup(self.app.flags, indexed, |h| h.switch == l)
+    }
+
+    fn option_by_short(&self, c: char) -> Option<&'a OptionHandler<'a>> {
+        let indexed = self.index.as_ref().map(|x| x.options.short.get(&c));
+        self.lookup(self.app.options, indexed, |h| h.switch == c)
+    }
+
+    fn option_by_long(&self, l: &str) -> Option<&'a OptionHandler<'a>> {
+        let indexed = self.index.as_ref().map(|x| x.options.long.get(l));
+        self.lookup(self.app.options, indexed, |h| h.switch == l)
+    }
+
+    fn trace(&self, message: Arguments) {
+        if self.debug {
+            eprintln!("[miniclap] {}", message);
+        }
+    }
+
+    fn assign_flag(&self, h: &FlagHandler<'a>) -> Result<()> {
+        self.trace(format_args!("  matched flag '{}'", h.name));
+        if h.is_exclusive {
+            self.exclusive.set(Some(h.switch));
+        } else {
+            self.others_given.set(true);
+        }
+        self.record(h.name, None);
+        h.assign()
+    }
+
+    fn assign_option(&self, h: &OptionHandler, value: OsString) -> Result<()> {
+        let lossy = value.to_string_lossy();
+        self.trace(format_args!("  matched option '{}' = '{}'", h.name, lossy));
+        self.others_given.set(true);
+        self.record(h.name, Some(&lossy));
+        h.assign_os(value)
+    }
+
+    /// The long switch most similar to `l`, if any is close enough to be a likely typo.
+    fn closest_long(&self, l: &str) -> Option<&'a str> {
+        let flags = self.app.flags.iter().map(|h| h.switch);
+        let options = self.app.options.iter().map(|h| h.switch);
+        flags
+            .chain(options)
+            .filter_map(|switch| match switch {
+                Switch::Long(long) | Switch::Both(_, long) => Some(long),
+                Switch::Short(_) => None,
+            })
+            .map(|long| (edit_distance(l, long), long))
+            .filter(|(distance, long)| *distance <= (long.len() / 3).max(1))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, long)| long)
+    }
+
+    fn next_arg(&mut self) -> Option<OsString> {
+        let arg = self.peeked.take().or_else(|| self.args.next());
+        self.num_taken += usize::from(arg.is_some());
+        arg
+    }
+
+    fn check_max_args(&self) -> Result<()> {
+        match self.app.max_args {
+            Some(max) if self.num_taken > max => Err(Error::too_many_args(max)),
+            _ => Ok(()),
+        }
+    }
+
+    fn peek_arg(&mut self) -> Option<&OsString> {
+        if self.peeked.is_none() {
+            self.peeked = self.args.next();
+        }
+        self.peeked.as_ref()
+    }
+
+    /// Takes the next argument as a value without interpreting it, so `--arg --` or `--arg -x`
+    /// gives the value `--` or `-x` rather than starting trailing mode or a switch.
+    fn next_value(&mut self, switch: Switch) -> Result<OsString> {
+        self.next_arg().ok_or_else(|| Error::missing_value(switch))
+    }
+
+    /// Gets the value for an option that was given without one attached.
+    fn detached_value(&mut self, h: &OptionHandler, switch: Switch) -> Result<OsString> {
+        // A lone `=` after the option joins it to the argument after that.
+        if self.app.allow_space_around_equals
+            && self.peek_arg().and_then(|x| x.to_str()) == Some("=")
+        {
+            self.next_arg();
+            return self.next_value(switch);
+        }
+        if let Some(value) = h.default_missing_value {
+            return Ok(value.into());
+        }
+        match (h.require_equals, h.is_optional_bool) {
+            (true, true) => return Ok("true".into()),
+            (true, false) => return Err(Error::detached_value(switch)),
+            (false, _) => (),
+        }
+        if !h.is_optional_bool {
+            return self.next_value(switch);
+        }
+        // Only take the next argument if it can only be meant as the value.
+        match self.peek_arg().and_then(|x| x.to_str()) {
+            Some("true") | Some("false") => self.next_value(switch),
+            _ => Ok("true".into()),
         }
     }
 
-    fn parse_short_flag(c: char, rest: &str, h: &FlagHandler) -> Result<()> {
+    /// Handles the long switch `name`, with the value attached to it if any. `token` is the whole
+    /// argument.
+    fn parse_long(&mut self, name: &str, value: Option<&str>, token: &str) -> Result<()> {
+        match (self.flag_by_long(name), self.option_by_long(name), value) {
+            (Some(h), _, None) => self.assign_flag(h),
+            (Some(_), _, Some(_)) => Err(Error::unexpected_value(Switch::Long(name))),
+            (_, Some(h), value) if h.is_raw => self.parse_raw(h, value.map(str::to_string)),
+            (_, Some(h), Some(value)) => self.assign_option(h, value.into()),
+            (_, Some(h), None) => {
+                let value = self.detached_value(h, Switch::Long(name))?;
+                self.assign_option(h, value)
+            }
+            _ if self.app.unknown_as_positional => self.unknown_as_positional(token),
+            _ if name.is_empty() => Err(Error::missing_switch_name(token)),
+            _ => {
+                let suggestion = self.closest_long(name).map(|l| format!("--{}", l));
+                Err(Error::unknown_switch(Switch::Long(name)).with_suggestion(suggestion))
+            }
+        }
+    }
+
+    fn parse_short_flag(&self, c: char, rest: &str, h: &FlagHandler<'a>) -> Result<()> {
         if rest.starts_with('=') {
             Err(Error::unexpected_value(Switch::Short(c)))
         } else {
-            h.assign()
+            self.assign_flag(h)
         }
     }
 
     fn parse_short_option(&mut self, c: char, rest: &str, h: &OptionHandler) -> Result<()> {
-        let value = match rest.chars().next() {
-            None => self.next_value(Switch::Short(c))?,
-            Some('=') => rest[1..].to_string(),
-            _ => rest.to_string(),
+        if h.is_raw {
+            let value = Some(rest.strip_prefix('=').unwrap_or(rest)).filter(|v| !v.is_empty());
+            return self.parse_raw(h, value.map(str::to_string));
+        }
+        // Like for a long option, only the first '=' is a separator, so `-n==5` gives `=5`.
+        let value = if rest.is_empty() {
+            self.detached_value(h, Switch::Short(c))?
+        } else {
+            rest.strip_prefix('=').unwrap_or(rest).into()
         };
-        h.assign(value)
+        self.assign_option(h, value)
+    }
+
+    /// A raw option captures every remaining argument verbatim, including any `--`.
+    fn parse_raw(&mut self, h: &OptionHandler, value: Option<String>) -> Result<()> {
+        if let Some(value) = value {
+            self.assign_option(h, value.into())?;
+        }
+        while let Some(arg_os) = self.next_arg() {
+            self.check_max_args()?;
+            self.assign_option(h, arg_os)?;
+        }
+        Ok(())
+    }
+
+    /// The handlers for a short switch, with at most one of them kept if it is both a flag and an
+    /// option: the flag unless `prefer_short_options` is set.
+    fn short_handlers(
+        &self,
+        c: char,
+    ) -> (Option<&'a FlagHandler<'a>>, Option<&'a OptionHandler<'a>>) {
+        match (self.flag_by_short(c), self.option_by_short(c)) {
+            (Some(_), Some(h)) if self.app.prefer_short_options => (None, Some(h)),
+            (Some(h), Some(_)) => (Some(h), None),
+            handlers => handlers,
+        }
     }
 
     fn parse_short(&mut self, c: char, rest: &str) -> Result<()> {
-        match (self.app.flag_by_short(c), self.app.option_by_short(c)) {
+        match self.short_handlers(c) {
             (Some(h), _) => {
-                Self::parse_short_flag(c, rest, h)?;
+                self.parse_short_flag(c, rest, h)?;
                 let chars = &mut rest.chars();
                 while let Some(c) = chars.next() {
-                    match (self.app.flag_by_short(c), self.app.option_by_short(c)) {
-                        (Some(h), _) => Self::parse_short_flag(c, chars.as_str(), h)?,
+                    match self.short_handlers(c) {
+                        (Some(h), _) => self.parse_short_flag(c, chars.as_str(), h)?,
                         (_, Some(h)) => return self.parse_short_option(c, chars.as_str(), h),
                         _ => return Err(Error::unknown_switch(Switch::Short(c))),
                     }
@@ -80,47 +353,283 @@ impl<'a> Parser<'a> {
                 Ok(())
             }
             (_, Some(h)) => self.parse_short_option(c, rest, h),
+            _ if self.app.unknown_as_positional => {
+                self.unknown_as_positional(&format!("-{}{}", c, rest))
+            }
+            // With no `-=` switch, the `=` is taken as separating a left out name from a value.
+            _ if c == '=' => Err(Error::missing_switch_name(&format!("-={}", rest))),
             _ => Err(Error::unknown_switch(Switch::Short(c))),
         }
     }
 
-    fn parse_positional(&mut self, arg: &str) -> Result<()> {
+    fn unknown_as_positional(&mut self, token: &str) -> Result<()> {
+        crate::warning::note(format!(
+            "Took unknown argument '{}' as a positional argument",
+            token
+        ));
+        self.parse_positional(token.into())
+    }
+
+    /// The positional handler for the next positional argument, if there is one.
+    fn positional(&self) -> Option<&'a PositionalHandler<'a>> {
         let h_by_index = self.app.positions.get(self.num_args);
         let h_last = self.app.positions.last().filter(|h| h.is_multiple);
-        match h_by_index.or(h_last) {
-            Some(h) => {
+        h_by_index.or(h_last)
+    }
+
+    /// Handles an argument that is not valid UTF-8. Switches must be valid UTF-8, so it can only
+    /// be a positional argument, whose handler decides whether it takes the value.
+    fn parse_non_utf8(&mut self, arg_os: OsString) -> Result<()> {
+        let arg = arg_os.to_string_lossy();
+        self.trace(format_args!("argument '{}' (invalid UTF-8)", arg));
+        if !self.is_trailing && arg.starts_with('-') {
+            return Err(Error::invalid_utf8());
+        }
+        self.parse_positional(arg_os)
+    }
+
+    fn parse_positional(&mut self, arg: OsString) -> Result<()> {
+        self.others_given.set(true);
+        if let (true, Some(h)) = (self.is_trailing, &self.app.trailing) {
+            self.trace(format_args!("  matched trailing '{}'", h.name));
+            self.record(h.name, Some(&arg.to_string_lossy()));
+            return h.assign_os(arg);
+        }
+        match (self.positional(), &self.app.subcommand) {
+            (Some(h), _) => {
+                self.trace(format_args!("  matched positional '{}'", h.name));
                 self.num_args += 1;
-                h.assign(arg.to_string())
+                self.record(h.name, Some(&arg.to_string_lossy()));
+                h.assign_os(arg)
+            }
+            // The subcommand consumes all remaining arguments. Nothing can be peeked here, since
+            // a peeked argument is always the next one handled by `parse`.
+            (None, Some(h)) => {
+                self.trace(format_args!("  matched subcommand '{}'", h.name));
+                let arg = arg.to_str().ok_or_else(Error::invalid_utf8)?;
+                self.record(h.name, Some(arg));
+                let max = match self.app.max_args {
+                    Some(max) => max,
+                    None => return h.assign(arg, self.args),
+                };
+                // The subcommand parses the rest itself, so it is only given what is left of the
+                // budget.
+                let mut args = Budget {
+                    args: &mut *self.args,
+                    remaining: max.saturating_sub(self.num_taken),
+                    exceeded: false,
+                };
+                let res = h.assign(arg, &mut args);
+                if args.exceeded {
+                    return Err(Error::too_many_args(max));
+                }
+                res
             }
-            None => Err(Error::too_many_positional(arg)),
+            (None, None) => match &self.app.overflow {
+                Some(h) => {
+                    self.trace(format_args!("  matched overflow '{}'", h.name));
+                    self.record(h.name, Some(&arg.to_string_lossy()));
+                    h.assign_os(arg)
+                }
+                None if self.app.ignore_extra_positionals => {
+                    self.trace(format_args!("  ignored extra positional"));
+                    crate::warning::warn(format!(
+                        "Ignored extra positional argument '{}'",
+                        arg.to_string_lossy()
+                    ));
+                    Ok(())
+                }
+                None => Err(Error::too_many_positional(
+                    &arg.to_string_lossy(),
+                    self.app.positions.len(),
+                )),
+            },
         }
     }
 
-    fn parse(mut self) -> Result<()> {
-        let _bin_name = self.args.next();
-        while let Some(arg_os) = self.args.next() {
-            let arg: &str = &arg_os.to_str().ok_or_else(Error::invalid_utf8)?;
+    fn parse(&mut self) -> Result<()> {
+        while let Some(arg_os) = self.next_arg() {
+            self.check_max_args()?;
+            let arg: &str = match arg_os.to_str() {
+                Some(arg) => arg,
+                None => {
+                    self.parse_non_utf8(arg_os)?;
+                    continue;
+                }
+            };
+            self.trace(format_args!("argument '{}'", arg));
 
-            // Match on the first two characters and remainder
-            let mut chars = arg.chars();
-            match (self.is_trailing, chars.next(), chars.next(), chars.as_str()) {
-                (false, Some('-'), Some('-'), "") => self.is_trailing = true,
-                (false, Some('-'), Some('-'), arg) => self.parse_long(arg)?,
-                (false, Some('-'), Some(c), rest) => self.parse_short(c, rest)?,
-                _ => self.parse_positional(arg)?,
+            let token = if self.is_trailing {
+                Token::Positional
+            } else {
+                classify(arg)
+            };
+            match token {
+                Token::Terminator => match self.app.batch_end {
+                    Some(batch_end) => {
+                        self.trace(format_args!("  end of batch"));
+                        self.num_args = 0;
+                        batch_end.assign()?;
+                    }
+                    None => self.is_trailing = true,
+                },
+                Token::Long { name, value } => self.parse_long(name, value, arg)?,
+                Token::Short { c, .. }
+                    if self.app.allow_negative_numbers
+                        && is_negative_number(arg)
+                        && self.flag_by_short(c).is_none()
+                        && self.option_by_short(c).is_none() =>
+                {
+                    self.parse_positional(arg_os)?
+                }
+                Token::Short { c, rest } => self.parse_short(c, rest)?,
+                Token::Positional => self.parse_positional(arg_os)?,
             }
         }
-        Ok(())
+        // The last argument may have been taken as a value.
+        self.check_max_args()?;
+        match self.exclusive.get() {
+            Some(switch) if self.others_given.get() => Err(Error::exclusive(switch)),
+            _ => Ok(()),
+        }
     }
 }
 
+/// The shape of a command-line argument, before any switch is looked up.
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    /// `--name` or `--name=value`.
+    Long {
+        name: &'a str,
+        value: Option<&'a str>,
+    },
+    /// `-c` followed by `rest`, which may hold more flags or the value of an option.
+    Short { c: char, rest: &'a str },
+    /// Anything not starting with `-`, and `-` alone.
+    Positional,
+    /// `--`, which starts trailing mode or ends a batch.
+    Terminator,
+}
+
+fn classify(token: &str) -> Token<'_> {
+    if token == "--" {
+        return Token::Terminator;
+    }
+    if let Some(long) = token.strip_prefix("--") {
+        // Split at the first '='. Names may contain '-' but never '=', so anything after it,
+        // including further '='s, is the value.
+        return match long.split_once('=') {
+            Some((name, value)) => Token::Long {
+                name,
+                value: Some(value),
+            },
+            None => Token::Long {
+                name: long,
+                value: None,
+            },
+        };
+    }
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some('-'), Some(c)) => Token::Short {
+            c,
+            rest: chars.as_str(),
+        },
+        _ => Token::Positional,
+    }
+}
+
+/// Whether `token` is a `-` followed by a number, such as `-5`, `-1.5` or `-.5e3`.
+fn is_negative_number(token: &str) -> bool {
+    match token.strip_prefix('-') {
+        Some(number) if number.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+            number.parse::<f64>().is_ok()
+        }
+        _ => false,
+    }
+}
+
+/// The number of single-character insertions, deletions, substitutions and transpositions of
+/// adjacent characters to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // d[i][j] is the distance between the first i characters of a and the first j of b.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Parses every item of `args` as an argument. Unlike `MiniClap::try_parse_from`, a leading
+/// binary name is not skipped, so callers holding a full argv must skip it themselves.
 pub fn parse_args(args: ArgOsIterator, app: &App) -> Result<()> {
     Parser::new(args, app).parse()
 }
 
+/// Like `parse_args`, but also records the arguments given so that they can be looked up by the
+/// names of their handlers. A subcommand's own arguments are not included.
+pub fn parse_matches(args: ArgOsIterator, app: &App) -> Result<ArgMatches> {
+    let mut parser = Parser::new(args, app);
+    parser.matches = Some(RefCell::default());
+    parser.parse()?;
+    Ok(parser.matches.take().unwrap_or_default().into_inner())
+}
+
+pub fn parse_subcommand<T: Subcommand>(args: ArgOsIterator) -> Result<T> {
+    match args.next().map(OsString::into_string) {
+        Some(Ok(name)) => T::__parse_subcommand(&name, args),
+        Some(Err(_)) => Err(Error::invalid_utf8()),
+        None => Err(Error::missing_subcommand()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{FlagAssign, ParsedAssign};
+
+    #[test]
+    fn classify_tokens() {
+        let long = |name, value| Token::Long { name, value };
+        let short = |c, rest| Token::Short { c, rest };
+        assert_eq!(classify("--"), Token::Terminator);
+        assert_eq!(classify("--verbose"), long("verbose", None));
+        assert_eq!(classify("--num=10"), long("num", Some("10")));
+        assert_eq!(classify("--num=a=b"), long("num", Some("a=b")));
+        assert_eq!(classify("--num="), long("num", Some("")));
+        assert_eq!(classify("---x"), long("-x", None));
+        assert_eq!(classify("-v"), short('v', ""));
+        assert_eq!(classify("-vvv"), short('v', "vv"));
+        assert_eq!(classify("-n=5"), short('n', "=5"));
+        assert_eq!(classify("-é1"), short('é', "1"));
+        assert_eq!(classify("-"), Token::Positional);
+        assert_eq!(classify(""), Token::Positional);
+        assert_eq!(classify("file.txt"), Token::Positional);
+        assert_eq!(classify("a-b"), Token::Positional);
+    }
+
+    #[test]
+    fn negative_numbers() {
+        for token in ["-5", "-1.5", "-.5", "-2e3", "-0"] {
+            assert!(is_negative_number(token), "{:?}", token);
+        }
+        for token in ["5", "-", "-x", "-5x", "-inf", "-nan", "--5", "-1-2"] {
+            assert!(!is_negative_number(token), "{:?}", token);
+        }
+    }
 
     #[test]
     fn simple() {
@@ -128,25 +637,30 @@ mod tests {
         let mut option = None;
         let mut pos = None;
         let res = parse_args(
-            &mut ["foo", "--num=10", "-vvv", "hello"]
-                .iter()
-                .map(OsString::from),
+            &mut ["--num=10", "-vvv", "hello"].iter().map(OsString::from),
             &App {
                 flags: &[FlagHandler {
                     name: "verbose",
                     switch: Switch::Short('v'),
+                    is_exclusive: false,
                     assign: &FlagAssign::new(|| verbose += 1),
                 }],
                 options: &[OptionHandler {
                     name: "num",
                     switch: Switch::Long("num"),
+                    is_raw: false,
+                    is_optional_bool: false,
+                    default_missing_value: None,
+                    require_equals: false,
                     assign: &ParsedAssign::new(|x| option = Some(x)),
                 }],
                 positions: &[PositionalHandler {
                     name: "foo",
                     is_multiple: false,
+                    is_lossy: false,
                     assign: &ParsedAssign::new(|x| pos = Some(x)),
                 }],
+                ..App::default()
             },
         );
         assert!(res.is_ok());
@@ -154,4 +668,271 @@ mod tests {
         assert_eq!(option, Some(10));
         assert_eq!(pos, Some("hello".to_string()));
     }
+
+    #[test]
+    fn flag_cluster_then_option() {
+        let mut verbose = 0;
+        let mut values = Vec::new();
+        let res = parse_args(
+            &mut ["-vox", "-vvo=y", "-vo", "z", "-voé"]
+                .iter()
+                .map(OsString::from),
+            &App {
+                flags: &[FlagHandler {
+                    name: "verbose",
+                    switch: Switch::Short('v'),
+                    is_exclusive: false,
+                    assign: &FlagAssign::new(|| verbose += 1),
+                }],
+                options: &[OptionHandler {
+                    name: "output",
+                    switch: Switch::Short('o'),
+                    is_raw: false,
+                    is_optional_bool: false,
+                    default_missing_value: None,
+                    require_equals: false,
+                    assign: &ParsedAssign::new(|x: String| values.push(x)),
+                }],
+                ..App::default()
+            },
+        );
+        assert!(res.is_ok());
+        assert_eq!(verbose, 5);
+        // The value is whatever follows the option's own character.
+        assert_eq!(values, ["x", "y", "z", "é"]);
+    }
+
+    #[test]
+    fn short_both_flag_and_option() {
+        let parse = |args: &[&str], prefer_short_options| {
+            let (mut extract, mut yes) = (0, 0);
+            let mut values = Vec::new();
+            let res = parse_args(
+                &mut args.iter().map(OsString::from),
+                &App {
+                    flags: &[
+                        FlagHandler {
+                            name: "extract",
+                            switch: Switch::Short('x'),
+                            is_exclusive: false,
+                            assign: &FlagAssign::new(|| extract += 1),
+                        },
+                        FlagHandler {
+                            name: "yes",
+                            switch: Switch::Short('y'),
+                            is_exclusive: false,
+                            assign: &FlagAssign::new(|| yes += 1),
+                        },
+                    ],
+                    options: &[OptionHandler {
+                        name: "exclude",
+                        switch: Switch::Short('x'),
+                        is_raw: false,
+                        is_optional_bool: false,
+                        default_missing_value: None,
+                        require_equals: false,
+                        assign: &ParsedAssign::new(|x: String| values.push(x)),
+                    }],
+                    prefer_short_options,
+                    ..App::default()
+                },
+            );
+            assert!(res.is_ok());
+            (extract, yes, values)
+        };
+        // By default the flag is taken, and the rest of the cluster are more flags.
+        let (extract, yes, values) = parse(&["-xy", "-yx"], false);
+        assert_eq!((extract, yes), (2, 2));
+        assert!(values.is_empty());
+        // Otherwise the option is taken, with the rest of the cluster or the next argument.
+        let (extract, yes, values) = parse(&["-xy", "-yx", "z"], true);
+        assert_eq!((extract, yes), (0, 1));
+        assert_eq!(values, ["y", "z"]);
+    }
+
+    #[test]
+    fn short_option_multibyte_value() {
+        let mut values = Vec::new();
+        let res = parse_args(
+            &mut ["-n=é", "-nü", "-n", "ß"].iter().map(OsString::from),
+            &App {
+                options: &[OptionHandler {
+                    name: "name",
+                    switch: Switch::Short('n'),
+                    is_raw: false,
+                    is_optional_bool: false,
+                    default_missing_value: None,
+                    require_equals: false,
+                    assign: &ParsedAssign::new(|x: String| values.push(x)),
+                }],
+                ..App::default()
+            },
+        );
+        assert!(res.is_ok());
+        assert_eq!(values, ["é", "ü", "ß"]);
+    }
+
+    #[test]
+    fn short_option_leading_equals() {
+        let mut values = Vec::new();
+        let res = parse_args(
+            &mut ["-n=5", "-n==5", "-n5", "-n=", "-n", "=5"]
+                .iter()
+                .map(OsString::from),
+            &App {
+                options: &[OptionHandler {
+                    name: "num",
+                    switch: Switch::Short('n'),
+                    is_raw: false,
+                    is_optional_bool: false,
+                    default_missing_value: None,
+                    require_equals: false,
+                    assign: &ParsedAssign::new(|x: String| values.push(x)),
+                }],
+                ..App::default()
+            },
+        );
+        assert!(res.is_ok());
+        assert_eq!(values, ["5", "=5", "5", "", "=5"]);
+    }
+
+    #[test]
+    fn long_option_hyphenated_equals() {
+        let mut max_count = None;
+        let mut filter = None;
+        let res = parse_args(
+            &mut ["--max-count=5", "--filter=a=b=c"]
+                .iter()
+                .map(OsString::from),
+            &App {
+                options: &[
+                    OptionHandler {
+                        name: "max_count",
+                        switch: Switch::Long("max-count"),
+                        is_raw: false,
+                        is_optional_bool: false,
+                        default_missing_value: None,
+                        require_equals: false,
+                        assign: &ParsedAssign::new(|x: u32| max_count = Some(x)),
+                    },
+                    OptionHandler {
+                        name: "filter",
+                        switch: Switch::Long("filter"),
+                        is_raw: false,
+                        is_optional_bool: false,
+                        default_missing_value: None,
+                        require_equals: false,
+                        assign: &ParsedAssign::new(|x: String| filter = Some(x)),
+                    },
+                ],
+                ..App::default()
+            },
+        );
+        assert!(res.is_ok());
+        assert_eq!(max_count, Some(5));
+        assert_eq!(filter.as_deref(), Some("a=b=c"));
+    }
+
+    #[test]
+    fn long_option_empty_value() {
+        let mut value = None;
+        let res = parse_args(
+            &mut ["--dry-run="].iter().map(OsString::from),
+            &App {
+                options: &[OptionHandler {
+                    name: "dry_run",
+                    switch: Switch::Long("dry-run"),
+                    is_raw: false,
+                    is_optional_bool: false,
+                    default_missing_value: None,
+                    require_equals: false,
+                    assign: &ParsedAssign::new(|x: String| value = Some(x)),
+                }],
+                ..App::default()
+            },
+        );
+        assert!(res.is_ok());
+        assert_eq!(value.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn too_many_positional() {
+        let mut first = None;
+        let err = parse_args(
+            &mut ["a", "b", "c"].iter().map(OsString::from),
+            &App {
+                positions: &[PositionalHandler {
+                    name: "first",
+                    is_multiple: false,
+                    is_lossy: false,
+                    assign: &ParsedAssign::new(|x: String| first = Some(x)),
+                }],
+                ..App::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Too many positional arguments, starting with 'b' (expected at most 1)"
+        );
+    }
+
+    #[test]
+    fn index_matches_scan() {
+        let log = &std::cell::RefCell::new(Vec::new());
+        let longs: Vec<String> = (0..20).map(|i| format!("flag{}", i)).collect();
+        let flag_assigns: Vec<_> = (0..20)
+            .map(|i| FlagAssign::new(move || log.borrow_mut().push(format!("flag{}", i))))
+            .collect();
+        let mut flags: Vec<_> = (0..20)
+            .map(|i| FlagHandler {
+                name: &longs[i],
+                switch: Switch::Both((b'a' + i as u8) as char, &longs[i]),
+                is_exclusive: false,
+                assign: &flag_assigns[i],
+            })
+            .collect();
+        // A later handler for the same switch is never used.
+        flags.push(flags[0]);
+        flags[20].name = "shadowed";
+        let level = ParsedAssign::new(|x: u8| log.borrow_mut().push(format!("level={}", x)));
+        let app = App {
+            flags: &flags,
+            options: &[OptionHandler {
+                name: "level",
+                switch: Switch::Both('L', "level"),
+                is_raw: false,
+                is_optional_bool: false,
+                default_missing_value: None,
+                require_equals: false,
+                assign: &level,
+            }],
+            ..App::default()
+        };
+        let argv = [
+            "-a",
+            "--flag19",
+            "-tsL3",
+            "--level=4",
+            "--flag0",
+            "--flag20",
+        ];
+        let mut results = Vec::new();
+        for use_index in [true, false] {
+            let args = &mut argv.iter().map(OsString::from);
+            let mut parser = Parser::new(args, &app);
+            assert!(parser.index.is_some());
+            if !use_index {
+                parser.index = None;
+            }
+            let err = parser.parse().unwrap_err();
+            results.push((log.take(), err.message));
+        }
+        assert_eq!(results[0], results[1]);
+        assert_eq!(
+            results[0].0,
+            ["flag0", "flag19", "flag19", "flag18", "level=3", "level=4", "flag0"]
+        );
+        assert_eq!(results[0].1, "Did not recognize argument '--flag20'");
+    }
 }
@@ -0,0 +1,98 @@
+use crate::{Error, Result};
+use std::cell::RefCell;
+use std::error::Error as StdError;
+use std::io::{self, BufRead, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+thread_local! {
+    /// Read from instead of the terminal while set by `with_prompt_reader`.
+    static READER: RefCell<Option<Box<dyn BufRead>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, answering any prompts in it with lines from `reader` instead of the terminal, such
+/// as in tests.
+pub fn with_prompt_reader<T>(reader: impl BufRead + 'static, f: impl FnOnce() -> T) -> T {
+    let outer = READER.with(|r| r.replace(Some(Box::new(reader))));
+    let result = f();
+    READER.with(|r| r.replace(outer));
+    result
+}
+
+/// Asks for the value of the argument `name` with `message` and parses the answer. Without a
+/// terminal to ask on, this is `None`, so that a required argument is reported missing.
+#[doc(hidden)]
+pub fn prompt<T>(name: &str, message: &str) -> Result<Option<T>>
+where
+    T: FromStr + 'static,
+    <T as FromStr>::Err: StdError + 'static,
+{
+    let value = READER.with(|r| match r.borrow_mut().as_mut() {
+        Some(reader) => read_line(reader).map(Some),
+        None => read_from_terminal(message),
+    });
+    let value = match value {
+        Ok(Some(value)) => value,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            return Err(Error::other_with_source(
+                "Failed to read a prompt",
+                Box::new(e),
+            ))
+        }
+    };
+    match value.parse() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(e) => Err(Error::parse_failed::<T>(name, &value, Box::new(e))),
+    }
+}
+
+/// Reads a line with echo turned off, or `None` if stdin is not a terminal.
+fn read_from_terminal(message: &str) -> io::Result<Option<String>> {
+    if !stdin_is_terminal() {
+        return Ok(None);
+    }
+    let mut stderr = io::stderr();
+    write!(stderr, "{}", message)?;
+    stderr.flush()?;
+    set_echo(false);
+    let value = read_line(&mut io::stdin().lock());
+    set_echo(true);
+    // The newline typed at the end was not echoed.
+    writeln!(stderr)?;
+    value.map(Some)
+}
+
+fn read_line(reader: &mut dyn BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let len = line.trim_end_matches(&['\r', '\n'][..]).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Whether stdin is a terminal. On Unix, this is whether `stty` can read its settings, which
+/// fails for anything else; elsewhere stdin is taken to be one.
+fn stdin_is_terminal() -> bool {
+    if !cfg!(unix) {
+        return true;
+    }
+    Command::new("stty")
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Turns echoing of typed characters on or off. Only done on Unix, with `stty`, so as not to
+/// depend on a terminal library; elsewhere the value is echoed.
+fn set_echo(on: bool) {
+    if cfg!(unix) {
+        let _ = Command::new("stty")
+            .arg(if on { "echo" } else { "-echo" })
+            .stdin(Stdio::inherit())
+            .status();
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{Error, Result};
+
+/// Integers that can be parsed in a base other than 10.
+#[doc(hidden)]
+pub trait FromStrRadix: Sized {
+    fn from_str_radix(src: &str, radix: u32) -> std::result::Result<Self, std::num::ParseIntError>;
+
+    /// Formats the integer in base `radix` without a prefix, so that it parses back.
+    fn to_str_radix(&self, radix: u32) -> String;
+}
+
+/// Formats a sign and magnitude in base `radix`.
+fn format_digits(negative: bool, mut magnitude: u128, radix: u32) -> String {
+    let mut digits = Vec::new();
+    loop {
+        let digit = (magnitude % u128::from(radix)) as u32;
+        digits.extend(std::char::from_digit(digit, radix));
+        magnitude /= u128::from(radix);
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if negative {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
+
+macro_rules! impl_from_str_radix {
+    ($($ty:ty => |$value:ident| $parts:expr),*) => {
+        $(
+            impl FromStrRadix for $ty {
+                #[inline]
+                fn from_str_radix(
+                    src: &str,
+                    radix: u32,
+                ) -> std::result::Result<Self, std::num::ParseIntError> {
+                    <$ty>::from_str_radix(src, radix)
+                }
+
+                fn to_str_radix(&self, radix: u32) -> String {
+                    let $value = *self;
+                    let (negative, magnitude) = $parts;
+                    format_digits(negative, magnitude as u128, radix)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($ty:ty),*) => {
+        impl_from_str_radix!($($ty => |x| (x < 0, x.unsigned_abs())),*);
+    };
+}
+
+macro_rules! impl_unsigned {
+    ($($ty:ty),*) => {
+        impl_from_str_radix!($($ty => |x| (false, x)),*);
+    };
+}
+
+impl_signed!(i8, i16, i32, i64, i128, isize);
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+
+/// Parses `value` as the argument `name` in base `radix`, allowing a `0x`, `0o` or `0b` prefix
+/// that matches the base.
+#[doc(hidden)]
+pub fn parse_radix<T>(name: &str, value: &str, radix: u32) -> Result<T>
+where
+    T: FromStrRadix + 'static,
+{
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", value.strip_prefix('+').unwrap_or(value)),
+    };
+    let prefix = match radix {
+        16 => Some("0x"),
+        8 => Some("0o"),
+        2 => Some("0b"),
+        _ => None,
+    };
+    let digits = prefix
+        .and_then(|p| {
+            digits
+                .strip_prefix(p)
+                .or_else(|| digits.strip_prefix(&*p.to_uppercase()))
+        })
+        .unwrap_or(digits);
+    T::from_str_radix(&format!("{}{}", sign, digits), radix)
+        .map_err(|e| Error::parse_failed::<T>(name, value, Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(parse_radix::<u8>("x", "ff", 16).unwrap(), 255);
+        assert_eq!(parse_radix::<u8>("x", "0xff", 16).unwrap(), 255);
+        assert_eq!(parse_radix::<u8>("x", "0XFF", 16).unwrap(), 255);
+        assert_eq!(parse_radix::<i32>("x", "-0x10", 16).unwrap(), -16);
+        assert_eq!(parse_radix::<u32>("x", "0o17", 8).unwrap(), 15);
+        assert_eq!(parse_radix::<u32>("x", "101", 2).unwrap(), 5);
+        // A prefix for another base is not stripped.
+        assert!(parse_radix::<u32>("x", "0x10", 8).is_err());
+        assert!(parse_radix::<u32>("x", "0x", 16).is_err());
+    }
+
+    #[test]
+    fn to_str_radix() {
+        assert_eq!(255u8.to_str_radix(16), "ff");
+        assert_eq!((-16i32).to_str_radix(16), "-10");
+        assert_eq!(i8::MIN.to_str_radix(2), "-10000000");
+        assert_eq!(0u32.to_str_radix(8), "0");
+        let value = u128::MAX.to_str_radix(36);
+        assert_eq!(parse_radix::<u128>("x", &value, 36).unwrap(), u128::MAX);
+        let value = i64::MIN.to_str_radix(7);
+        assert_eq!(parse_radix::<i64>("x", &value, 7).unwrap(), i64::MIN);
+    }
+}
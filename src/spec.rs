@@ -0,0 +1,123 @@
+use crate::Switch;
+
+/// What kind of argument an `ArgInfo` describes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A switch without a value, such as `--verbose`.
+    Flag,
+    /// A switch with a value, such as `--level 3`.
+    Option,
+    /// An argument without a switch.
+    Positional,
+    /// The subcommand that takes all of the remaining arguments.
+    Subcommand,
+}
+
+/// Describes an argument of a type deriving `MiniClap`, for tools such as help or completion
+/// generators. It only holds `&'static` data so that `MiniClap::SPEC` can be a constant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ArgInfo {
+    /// The name of the field.
+    pub name: &'static str,
+    pub kind: ArgKind,
+    /// How the argument is named on the command line, if it is a flag or option.
+    pub switch: Option<Switch<'static>>,
+    /// Parsing fails if the argument is not given.
+    pub is_required: bool,
+    /// The argument may be given more than once.
+    pub is_multiple: bool,
+    /// The environment variable read when the argument is not given.
+    pub env: Option<&'static str>,
+    /// The type of the field as written in the struct, such as `Option<u32>`.
+    pub type_name: &'static str,
+    /// The values given by `default_value`, as they would be written on the command line.
+    pub default_values: &'static [&'static str],
+}
+
+/// Writes a JSON document describing each argument in `spec`, such as `MiniClap::SPEC`, for tools
+/// that read the arguments of a program without linking to it:
+///
+/// ```
+/// use miniclap::MiniClap;
+///
+/// #[derive(MiniClap)]
+/// struct Opts {
+///     #[miniclap(short, long, default_value = 3)]
+///     level: u8,
+///
+///     files: Vec<String>,
+/// }
+///
+/// let schema = miniclap::generate_schema(Opts::SPEC);
+/// assert!(schema.contains(r#""name": "level", "kind": "option", "short": "l""#));
+/// ```
+///
+/// Doc comments are not kept by the derive, so arguments have no description.
+pub fn generate_schema(spec: &[ArgInfo]) -> String {
+    let mut json = String::from("{\n  \"arguments\": [");
+    for (i, arg) in spec.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let kind = match arg.kind {
+            ArgKind::Flag => "flag",
+            ArgKind::Option => "option",
+            ArgKind::Positional => "positional",
+            ArgKind::Subcommand => "subcommand",
+        };
+        let (short, long) = match arg.switch {
+            Some(Switch::Short(short)) => (Some(short), None),
+            Some(Switch::Long(long)) => (None, Some(long)),
+            Some(Switch::Both(short, long)) => (Some(short), Some(long)),
+            None => (None, None),
+        };
+        let defaults: Vec<_> = arg.default_values.iter().map(|x| json_string(x)).collect();
+        json.push_str(&format!(
+            "\n    {{\"name\": {}, \"kind\": \"{}\", \"short\": {}, \"long\": {}, \"type\": {}, \
+            \"required\": {}, \"multiple\": {}, \"default\": [{}], \"env\": {}}}",
+            json_string(arg.name),
+            kind,
+            short.map_or("null".to_string(), |c| json_string(&c.to_string())),
+            long.map_or("null".to_string(), json_string),
+            json_string(arg.type_name),
+            arg.is_required,
+            arg.is_multiple,
+            defaults.join(", "),
+            arg.env.map_or("null".to_string(), json_string),
+        ));
+    }
+    if !spec.is_empty() {
+        json.push_str("\n  ");
+    }
+    json.push_str("]\n}\n");
+    json
+}
+
+/// Quotes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Describes a subcommand of an enum deriving `MiniClap`, for help and completion generators.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SubcommandInfo {
+    /// The name of the subcommand, which is the variant name in kebab case.
+    pub name: &'static str,
+    /// Other names that select the subcommand and are meant to be shown. Aliases declared with
+    /// `hidden_alias` select it too, but are left out.
+    pub aliases: &'static [&'static str],
+}
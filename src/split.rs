@@ -0,0 +1,81 @@
+use crate::{Error, Result};
+
+/// Splits a command line into words the way a POSIX shell would, without any expansions.
+///
+/// Words are separated by whitespace. Single quotes keep everything up to the closing quote
+/// literally, and double quotes do too except that `\"` and `\\` are escapes. Outside of quotes,
+/// a backslash keeps the next character literally. An unclosed quote or a trailing backslash is
+/// an error.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    // Set once the current word has started, so that `''` still produces an empty word.
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(Error::other("Unclosed single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(Error::other("Unclosed double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(Error::other("Unclosed double quote")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(Error::other("Trailing backslash")),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_and_escapes() {
+        let words = split_words(r#"  a 'b c' "d \"e\" \\ \n" f\ g '' h"i"j  "#).unwrap();
+        assert_eq!(words, ["a", "b c", r#"d "e" \ \n"#, "f g", "", "hij"]);
+    }
+
+    #[test]
+    fn unbalanced() {
+        assert_eq!(
+            split_words("a 'b").unwrap_err().message,
+            "Unclosed single quote"
+        );
+        assert_eq!(
+            split_words(r#"a "b\""#).unwrap_err().message,
+            "Unclosed double quote"
+        );
+        assert_eq!(
+            split_words(r"a\").unwrap_err().message,
+            "Trailing backslash"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::Result;
+use std::cell::RefCell;
+
+thread_local! {
+    /// The warnings of the parse in progress on this thread, if they are being collected.
+    static COLLECTED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// A parsed value along with any warnings from parsing it. Returned by
+/// `MiniClap::try_parse_with_warnings`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseResult<T> {
+    pub value: T,
+    /// In the order they happened, such as for deprecated arguments that were given.
+    pub warnings: Vec<String>,
+    /// The work done by the parse, including that of any subcommand.
+    #[cfg(feature = "metrics")]
+    pub metrics: crate::ParseMetrics,
+}
+
+/// Runs `parse`, collecting the warnings reported during it instead of printing them.
+pub(crate) fn collect<T>(parse: impl FnOnce() -> Result<T>) -> Result<ParseResult<T>> {
+    let outer = COLLECTED.with(|collected| collected.replace(Some(Vec::new())));
+    #[cfg(feature = "metrics")]
+    let (value, metrics) = crate::metrics::collect(parse);
+    #[cfg(not(feature = "metrics"))]
+    let value = parse();
+    let warnings = COLLECTED.with(|collected| collected.replace(outer));
+    Ok(ParseResult {
+        value: value?,
+        warnings: warnings.unwrap_or_default(),
+        #[cfg(feature = "metrics")]
+        metrics,
+    })
+}
+
+/// Reports a warning to the collecting parse, or else prints it to stderr.
+#[doc(hidden)]
+pub fn warn(message: String) {
+    if let Err(message) = try_collect(message) {
+        eprintln!("warning: {}", message);
+    }
+}
+
+/// Reports something only worth mentioning to a collecting parse, such as a recovery the app
+/// opted into.
+pub(crate) fn note(message: String) {
+    let _ = try_collect(message);
+}
+
+fn try_collect(message: String) -> std::result::Result<(), String> {
+    COLLECTED.with(|collected| match collected.borrow_mut().as_mut() {
+        Some(warnings) => {
+            warnings.push(message);
+            Ok(())
+        }
+        None => Err(message),
+    })
+}
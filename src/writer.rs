@@ -0,0 +1,75 @@
+/// Collects the arguments written by a derived `to_args`, so that the switches of flattened
+/// structs can be written before the positional arguments that come ahead of theirs.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct ArgWriter {
+    switches: Vec<String>,
+    positionals: Vec<String>,
+    subcommand: Vec<String>,
+    /// Set if the struct has a trailing field, even with no values.
+    trailing: Option<Vec<String>>,
+    raw: Vec<String>,
+}
+
+impl ArgWriter {
+    pub fn flag(&mut self, switch: &str) {
+        self.switches.push(switch.to_string());
+    }
+
+    pub fn option(&mut self, switch: &str, value: String) {
+        self.switches.push(switch.to_string());
+        self.switches.push(value);
+    }
+
+    /// Writes the value attached to the switch, for options that require it to be.
+    pub fn attached(&mut self, switch: &str, value: String) {
+        self.switches.push(format!("{}={}", switch, value));
+    }
+
+    pub fn positional(&mut self, value: String) {
+        self.positionals.push(value);
+    }
+
+    pub fn subcommand(&mut self, args: Vec<String>) {
+        self.subcommand = args;
+    }
+
+    pub fn trailing(&mut self, values: Vec<String>) {
+        self.trailing = Some(values);
+    }
+
+    /// Writes a raw option, which takes every argument after it and so comes last.
+    pub fn raw(&mut self, switch: &str, values: Vec<String>) {
+        if !values.is_empty() {
+            self.raw.push(switch.to_string());
+            self.raw.extend(values);
+        }
+    }
+
+    pub fn finish(self) -> Vec<String> {
+        let mut args = self.switches;
+        // A positional that looks like a switch needs a `--` before it, unless that would send the
+        // arguments after it elsewhere.
+        let needs_terminator = self.trailing.is_none()
+            && self.subcommand.is_empty()
+            && self.raw.is_empty()
+            && self
+                .positionals
+                .iter()
+                .any(|value| value.starts_with('-') && value != "-");
+        if needs_terminator {
+            args.push("--".to_string());
+        }
+        args.extend(self.positionals);
+        args.extend(self.subcommand);
+        match self.trailing {
+            Some(values) if !values.is_empty() => {
+                args.push("--".to_string());
+                args.extend(values);
+            }
+            _ => (),
+        }
+        args.extend(self.raw);
+        args
+    }
+}
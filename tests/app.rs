@@ -0,0 +1,262 @@
+use miniclap::{App, FlagAssign, FlagHandler, OptionHandler, ParsedAssign, Switch};
+use std::ffi::OsString;
+
+fn args<'a>(args: &'a [&'a str]) -> impl Iterator<Item = OsString> + 'a {
+    args.iter().map(OsString::from)
+}
+
+#[test]
+fn switch_display() {
+    assert_eq!(Switch::Short('v').to_string(), "-v");
+    assert_eq!(Switch::Long("verbose").to_string(), "--verbose");
+    assert_eq!(Switch::Both('v', "verbose").to_string(), "-v/--verbose");
+}
+
+#[test]
+fn switch_matches_short_and_long() {
+    assert_eq!(Switch::Both('v', "verbose"), 'v');
+    assert_eq!(Switch::Both('v', "verbose"), "verbose");
+    assert_ne!(Switch::Short('v'), "v");
+    assert_ne!(Switch::Long("verbose"), 'v');
+}
+
+#[test]
+fn handlers_with_public_switch() {
+    let mut verbose = 0;
+    let mut level = None;
+    let res = miniclap::parse_args(
+        &mut args(&["-v", "--verbose", "-l", "3"]),
+        &App {
+            flags: &[FlagHandler {
+                name: "verbose",
+                switch: Switch::Both('v', "verbose"),
+                is_exclusive: false,
+                assign: &FlagAssign::new(|| verbose += 1),
+            }],
+            options: &[OptionHandler {
+                name: "level",
+                switch: Switch::Short('l'),
+                is_raw: false,
+                is_optional_bool: false,
+                default_missing_value: None,
+                require_equals: false,
+                assign: &ParsedAssign::new(|x: u8| level = Some(x)),
+            }],
+            ..App::default()
+        },
+    );
+    assert!(res.is_ok());
+    assert_eq!(verbose, 2);
+    assert_eq!(level, Some(3));
+}
+
+#[test]
+fn error_names_the_switch() {
+    let err = miniclap::parse_args(
+        &mut args(&["--level"]),
+        &App {
+            options: &[OptionHandler {
+                name: "level",
+                switch: Switch::Both('l', "level"),
+                is_raw: false,
+                is_optional_bool: false,
+                default_missing_value: None,
+                require_equals: false,
+                assign: &ParsedAssign::new(|_: u8| ()),
+            }],
+            ..App::default()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err.message, "Missing value for '--level'");
+}
+
+#[test]
+fn parse_args_does_not_skip_bin_name() {
+    let mut positions = Vec::new();
+    let mut argv = args(&["bin", "a", "b"]);
+    // A full argv must have its binary name skipped by the caller.
+    assert_eq!(argv.next(), Some("bin".into()));
+    let res = miniclap::parse_args(
+        &mut argv,
+        &App {
+            positions: &[miniclap::PositionalHandler {
+                name: "pos",
+                is_multiple: true,
+                is_lossy: false,
+                assign: &ParsedAssign::new(|x: String| positions.push(x)),
+            }],
+            ..App::default()
+        },
+    );
+    assert!(res.is_ok());
+    assert_eq!(positions, ["a", "b"]);
+}
+
+#[test]
+fn overflow_collects_extra_positionals() {
+    let mut first = None;
+    let mut rest = Vec::new();
+    let res = miniclap::parse_args(
+        &mut args(&["a", "b", "--", "-c"]),
+        &App {
+            positions: &[miniclap::PositionalHandler {
+                name: "first",
+                is_multiple: false,
+                is_lossy: false,
+                assign: &ParsedAssign::new(|x: String| first = Some(x)),
+            }],
+            overflow: Some(miniclap::PositionalHandler {
+                name: "rest",
+                is_multiple: true,
+                is_lossy: false,
+                assign: &ParsedAssign::new(|x: String| rest.push(x)),
+            }),
+            ..App::default()
+        },
+    );
+    assert!(res.is_ok());
+    assert_eq!(first.as_deref(), Some("a"));
+    assert_eq!(rest, ["b", "-c"]);
+}
+
+#[test]
+fn matches_by_name() {
+    let ignore_flag = FlagAssign::new(|| ());
+    let ignore_string = ParsedAssign::new(|_: String| ());
+    let matches = miniclap::parse_matches(
+        &mut args(&["-vv", "--level", "3", "a.txt", "--level=4", "b.txt"]),
+        &App {
+            flags: &[FlagHandler {
+                name: "verbose",
+                switch: Switch::Short('v'),
+                is_exclusive: false,
+                assign: &ignore_flag,
+            }],
+            options: &[OptionHandler {
+                name: "level",
+                switch: Switch::Long("level"),
+                is_raw: false,
+                is_optional_bool: false,
+                default_missing_value: None,
+                require_equals: false,
+                assign: &ParsedAssign::new(|_: u8| ()),
+            }],
+            positions: &[miniclap::PositionalHandler {
+                name: "files",
+                is_multiple: true,
+                is_lossy: false,
+                assign: &ignore_string,
+            }],
+            ..App::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(matches.occurrences_of("verbose"), 2);
+    assert_eq!(matches.value_of("verbose"), None);
+    assert_eq!(matches.occurrences_of("level"), 2);
+    assert_eq!(matches.value_of("level"), Some("4"));
+    assert_eq!(matches.values_of("files"), ["a.txt", "b.txt"]);
+    assert_eq!(matches.occurrences_of("missing"), 0);
+    assert_eq!(matches.value_of("missing"), None);
+}
+
+#[test]
+fn parse_args_pulls_lazily() {
+    let pulled = std::cell::Cell::new(0);
+    // Stands in for arguments arriving over a channel: asking for more than were sent would
+    // block, so here it panics instead.
+    let mut stream = ["-v", "--bogus"]
+        .iter()
+        .map(OsString::from)
+        .chain(std::iter::from_fn(|| panic!("pulled past the error")));
+    let mut counted = std::iter::from_fn(|| {
+        pulled.set(pulled.get() + 1);
+        stream.next()
+    });
+    let err = miniclap::parse_args(
+        &mut counted,
+        &App {
+            flags: &[FlagHandler {
+                name: "verbose",
+                switch: Switch::Short('v'),
+                is_exclusive: false,
+                assign: &FlagAssign::new(|| ()),
+            }],
+            ..App::default()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err.message, "Did not recognize argument '--bogus'");
+    assert_eq!(pulled.get(), 2);
+}
+
+#[test]
+fn collect_assign() {
+    let files = miniclap::CollectAssign::new();
+    let res = miniclap::parse_args(
+        &mut args(&["a", "b", "--", "-c"]),
+        &App {
+            positions: &[miniclap::PositionalHandler {
+                name: "files",
+                is_multiple: true,
+                is_lossy: false,
+                assign: &files,
+            }],
+            ..App::default()
+        },
+    );
+    assert!(res.is_ok());
+    assert_eq!(files.values().collect::<Vec<_>>(), ["a", "b", "-c"]);
+    assert_eq!(files.into_values(), ["a", "b", "-c"]);
+}
+
+#[test]
+fn batches_separated_by_terminator() {
+    let log = std::cell::RefCell::new(Vec::new());
+    let res = miniclap::parse_args(
+        &mut args(&["a", "b", "c", "--", "d", "-v", "--", "--"]),
+        &App {
+            flags: &[FlagHandler {
+                name: "verbose",
+                switch: Switch::Short('v'),
+                is_exclusive: false,
+                assign: &FlagAssign::new(|| log.borrow_mut().push("-v".to_string())),
+            }],
+            positions: &[
+                miniclap::PositionalHandler {
+                    name: "program",
+                    is_multiple: false,
+                    is_lossy: false,
+                    assign: &ParsedAssign::new(|x: String| {
+                        log.borrow_mut().push(format!("program={}", x))
+                    }),
+                },
+                miniclap::PositionalHandler {
+                    name: "args",
+                    is_multiple: true,
+                    is_lossy: false,
+                    assign: &ParsedAssign::new(|x: String| {
+                        log.borrow_mut().push(format!("arg={}", x))
+                    }),
+                },
+            ],
+            batch_end: Some(&FlagAssign::new(|| log.borrow_mut().push("--".to_string()))),
+            ..App::default()
+        },
+    );
+    assert!(res.is_ok());
+    assert_eq!(
+        log.into_inner(),
+        [
+            "program=a",
+            "arg=b",
+            "arg=c",
+            "--",
+            "program=d",
+            "-v",
+            "--",
+            "--"
+        ]
+    );
+}
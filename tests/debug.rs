@@ -0,0 +1,46 @@
+use miniclap::MiniClap;
+use std::process::Command;
+
+const CHILD_ENV: &str = "MINICLAP_DEBUG_TEST_CHILD";
+
+#[allow(dead_code)]
+#[derive(MiniClap)]
+struct Opts {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(long)]
+    num: Option<u32>,
+
+    file: Option<String>,
+}
+
+/// Runs inside the child process spawned by `debug_env_logs_matches`.
+#[test]
+fn debug_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    Opts::try_parse_from(["test", "-v", "--num", "3", "in.txt"]).unwrap();
+}
+
+#[test]
+fn debug_env_logs_matches() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["debug_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .env("MINICLAP_DEBUG", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = "\
+[miniclap] argument '-v'
+[miniclap]   matched flag 'verbose'
+[miniclap] argument '--num'
+[miniclap]   matched option 'num' = '3'
+[miniclap] argument 'in.txt'
+[miniclap]   matched positional 'file'
+";
+    assert!(stderr.contains(expected), "stderr: {:?}", stderr);
+}
@@ -0,0 +1,2367 @@
+use miniclap::MiniClap;
+use std::ffi::OsString;
+
+#[derive(Debug, MiniClap)]
+struct Profile {
+    #[miniclap(long, overrides_with = "release")]
+    debug: bool,
+
+    #[miniclap(long)]
+    release: bool,
+}
+
+#[test]
+fn overrides_with_last_wins() {
+    let opts = Profile::try_parse_from(["test", "--debug", "--release"]).unwrap();
+    assert!(!opts.debug);
+    assert!(opts.release);
+
+    let opts = Profile::try_parse_from(["test", "--release", "--debug"]).unwrap();
+    assert!(opts.debug);
+    assert!(!opts.release);
+
+    let opts = Profile::try_parse_from(["test", "--debug"]).unwrap();
+    assert!(opts.debug);
+    assert!(!opts.release);
+}
+
+#[derive(Debug, MiniClap)]
+struct Ranged {
+    #[miniclap(long, range = "1..=100")]
+    percent: u8,
+
+    #[miniclap(long, range = "-5..5")]
+    offset: Option<i32>,
+
+    #[miniclap(long, range = "0..10")]
+    digit: Vec<u32>,
+}
+
+#[test]
+fn range_boundaries() {
+    let opts = Ranged::try_parse_from(["test", "--percent=1", "--offset=-5"]).unwrap();
+    assert_eq!(opts.percent, 1);
+    assert_eq!(opts.offset, Some(-5));
+
+    let opts = Ranged::try_parse_from(["test", "--percent=100", "--digit=0", "--digit=9"]).unwrap();
+    assert_eq!(opts.percent, 100);
+    assert_eq!(opts.offset, None);
+    assert_eq!(opts.digit, vec![0, 9]);
+}
+
+#[test]
+fn range_out_of_bounds() {
+    let err = Ranged::try_parse_from(["test", "--percent=0"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::OutOfRange));
+    assert_eq!(
+        err.message,
+        "Argument 'percent' must be in the range 1..=100, got '0'"
+    );
+    assert!(Ranged::try_parse_from(["test", "--percent=101"]).is_err());
+    assert!(Ranged::try_parse_from(["test", "--percent=5", "--offset=5"]).is_err());
+    assert!(Ranged::try_parse_from(["test", "--percent=5", "--digit=3", "--digit=10"]).is_err());
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(default_long)]
+struct DefaultLong {
+    count: u32,
+
+    #[miniclap(short)]
+    verbose: bool,
+
+    #[miniclap(long = "out")]
+    output: Option<String>,
+
+    #[miniclap(positional)]
+    input: String,
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(default_long = false)]
+struct NoDefaultLong {
+    count: u32,
+}
+
+#[test]
+fn default_long_names_bare_fields() {
+    let opts = DefaultLong::try_parse_from(["test", "--count", "3", "-v", "in.txt"]).unwrap();
+    assert_eq!(opts.count, 3);
+    assert!(opts.verbose);
+    assert_eq!(opts.output, None);
+    assert_eq!(opts.input, "in.txt");
+
+    let opts = DefaultLong::try_parse_from(["test", "--out=x", "in.txt", "--count=1"]).unwrap();
+    assert_eq!(opts.output, Some("x".into()));
+    assert_eq!(opts.count, 1);
+}
+
+#[test]
+fn default_long_is_opt_in() {
+    let opts = NoDefaultLong::try_parse_from(["test", "3"]).unwrap();
+    assert_eq!(opts.count, 3);
+    assert!(NoDefaultLong::try_parse_from(["test", "--count=3"]).is_err());
+}
+
+#[derive(Debug, MiniClap)]
+struct Wrapper {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(short, long, raw)]
+    raw: Vec<String>,
+
+    tool: Option<String>,
+}
+
+#[test]
+fn raw_captures_remaining_args() {
+    let opts = Wrapper::try_parse_from(["cmd", "ssh", "--raw", "-x", "--y", "z"]).unwrap();
+    assert_eq!(opts.tool, Some("ssh".into()));
+    assert_eq!(opts.raw, ["-x", "--y", "z"]);
+
+    let opts = Wrapper::try_parse_from(["cmd", "-v", "--raw=-x", "--", "-v"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.raw, ["-x", "--", "-v"]);
+
+    let opts = Wrapper::try_parse_from(["cmd", "-vr", "a", "b"]).unwrap();
+    assert_eq!(opts.raw, ["a", "b"]);
+    assert_eq!(opts.tool, None);
+
+    let opts = Wrapper::try_parse_from(["cmd", "--raw"]).unwrap();
+    assert!(opts.raw.is_empty());
+}
+
+#[test]
+fn raw_not_active_after_trailing_marker() {
+    let opts = Wrapper::try_parse_from(["cmd", "--", "--raw"]).unwrap();
+    assert_eq!(opts.tool, Some("--raw".into()));
+    assert!(opts.raw.is_empty());
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(matches)]
+struct Server {
+    #[miniclap(long, default_value = 8080)]
+    port: u16,
+
+    #[miniclap(long)]
+    tls: bool,
+
+    host: String,
+}
+
+#[test]
+fn matches_before_building() {
+    let mut matches = Server::matches_from(["test", "--tls", "localhost"]).unwrap();
+    assert_eq!(*matches.port(), 8080);
+    assert!(*matches.tls());
+    assert_eq!(matches.host().as_deref(), Some("localhost"));
+
+    // TLS defaults to a different port unless one was given.
+    if *matches.tls() && *matches.port() == 8080 {
+        *matches.port_mut() = 8443;
+    }
+    let opts = matches.into_opts().unwrap();
+    assert_eq!(opts.port, 8443);
+    assert!(opts.tls);
+    assert_eq!(opts.host, "localhost");
+}
+
+#[test]
+fn matches_still_validate_on_build() {
+    let mut matches = Server::matches_from(["test", "--port=1"]).unwrap();
+    assert_eq!(*matches.host(), None);
+    let err = Server::matches_from(["test", "--port=1"])
+        .unwrap()
+        .into_opts()
+        .unwrap_err();
+    assert_eq!(err.message, "Missing required argument 'host'");
+
+    *matches.host_mut() = Some("example.com".into());
+    assert_eq!(matches.into_opts().unwrap().host, "example.com");
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(matches)]
+struct Verbosity {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(short)]
+    quiet: bool,
+}
+
+#[test]
+fn flag_occurrences() {
+    let matches = Verbosity::matches_from(["test", "-vvv", "--verbose"]).unwrap();
+    assert_eq!(matches.verbose_occurrences(), 4);
+    assert_eq!(matches.quiet_occurrences(), 0);
+    assert!(matches.into_opts().unwrap().verbose);
+
+    let matches = Verbosity::matches_from(["test", "-qvq"]).unwrap();
+    assert_eq!(matches.verbose_occurrences(), 1);
+    assert_eq!(matches.quiet_occurrences(), 2);
+    assert!(matches.into_opts().unwrap().quiet);
+}
+
+/// Without `matches`, the names the derive would generate are free to use.
+#[derive(Debug, MiniClap)]
+struct Plain {
+    #[miniclap(short)]
+    verbose: bool,
+
+    #[miniclap(long)]
+    verbose_occurrences: Option<usize>,
+}
+
+struct PlainMatches(Plain);
+
+impl Plain {
+    fn matches_from(args: &[&str]) -> PlainMatches {
+        PlainMatches(Plain::try_parse_from(args).unwrap())
+    }
+}
+
+#[test]
+fn matches_are_opt_in() {
+    let PlainMatches(opts) = Plain::matches_from(&["test", "-v", "--verbose_occurrences=2"]);
+    assert!(opts.verbose);
+    assert_eq!(opts.verbose_occurrences, Some(2));
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(auto_negate_bools)]
+struct Negatable {
+    #[miniclap(long)]
+    color: bool,
+
+    #[miniclap(short)]
+    quiet: bool,
+}
+
+#[test]
+fn auto_negate_bools() {
+    let opts = Negatable::try_parse_from(["test", "--color"]).unwrap();
+    assert!(opts.color);
+
+    let opts = Negatable::try_parse_from(["test", "--color", "--no-color"]).unwrap();
+    assert!(!opts.color);
+
+    let opts = Negatable::try_parse_from(["test", "--no-color", "--color", "-q"]).unwrap();
+    assert!(opts.color);
+    assert!(opts.quiet);
+
+    let opts = Negatable::try_parse_from(["test", "--no-color"]).unwrap();
+    assert!(!opts.color);
+
+    // Short-only flags have no long name to negate.
+    assert!(Negatable::try_parse_from(["test", "--no-q"]).is_err());
+    assert!(Negatable::try_parse_from(["test", "--no-quiet"]).is_err());
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(matches)]
+struct BinName {
+    #[miniclap(long)]
+    num: Option<u32>,
+
+    pos: Vec<String>,
+}
+
+#[test]
+fn bin_name_is_skipped_by_try_parse_from() {
+    let opts = BinName::try_parse_from(["bin", "--num=1", "a"]).unwrap();
+    assert_eq!(opts.num, Some(1));
+    assert_eq!(opts.pos, ["a"]);
+
+    let opts = BinName::matches_from(["bin", "a"])
+        .unwrap()
+        .into_opts()
+        .unwrap();
+    assert_eq!(opts.pos, ["a"]);
+}
+
+#[test]
+fn no_bin_name_with_try_parse_without_bin_name() {
+    let opts = BinName::try_parse_without_bin_name(["--num=1", "a"]).unwrap();
+    assert_eq!(opts.num, Some(1));
+    assert_eq!(opts.pos, ["a"]);
+
+    let opts = BinName::try_parse_without_bin_name(["bin", "a"]).unwrap();
+    assert_eq!(opts.pos, ["bin", "a"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct Conditional {
+    #[miniclap(long)]
+    mode: Option<String>,
+
+    #[miniclap(long, required_if_eq(mode = "server"))]
+    port: Option<u16>,
+
+    #[miniclap(long, requires_all(cert, key))]
+    tls: bool,
+
+    #[miniclap(long)]
+    cert: Option<String>,
+
+    #[miniclap(long)]
+    key: Option<String>,
+}
+
+#[test]
+fn required_if_eq() {
+    let opts = Conditional::try_parse_from(["test", "--mode", "client"]).unwrap();
+    assert_eq!(opts.mode.as_deref(), Some("client"));
+    assert_eq!(opts.port, None);
+
+    let err = Conditional::try_parse_from(["test", "--mode", "server"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        miniclap::ErrorKind::MissingRequiredArgument
+    ));
+    assert_eq!(
+        err.message,
+        "Missing required argument 'port' (required when 'mode' is 'server')"
+    );
+
+    let opts = Conditional::try_parse_from(["test", "--mode", "server", "--port=80"]).unwrap();
+    assert_eq!(opts.port, Some(80));
+}
+
+#[test]
+fn requires_all() {
+    let err = Conditional::try_parse_from(["test", "--tls", "--cert=a"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::MissingRequirement));
+    assert_eq!(err.message, "Argument 'tls' requires 'key'");
+
+    let opts = Conditional::try_parse_from(["test", "--tls", "--cert=a", "--key=b"]).unwrap();
+    assert!(opts.tls);
+    assert_eq!(opts.cert.as_deref(), Some("a"));
+    assert_eq!(opts.key.as_deref(), Some("b"));
+}
+
+#[test]
+fn try_parse_from_os_iterator() {
+    let argv = vec![OsString::from("bin"), "--num=2".into(), "a".into()];
+    let opts = BinName::try_parse_from_os(argv.into_iter()).unwrap();
+    assert_eq!(opts.num, Some(2));
+    assert_eq!(opts.pos, ["a"]);
+}
+
+#[test]
+fn try_parse_from_strings() {
+    let argv: Vec<String> = vec!["bin".into(), "--num=2".into(), "é".into()];
+    let opts = BinName::try_parse_from_strings(argv).unwrap();
+    assert_eq!(opts.num, Some(2));
+    assert_eq!(opts.pos, ["é"]);
+
+    let err = BinName::try_parse_from_strings(vec!["bin".into(), "--num=x".to_string()]);
+    assert!(matches!(
+        err.unwrap_err().kind,
+        miniclap::ErrorKind::ParseFailed
+    ));
+}
+
+#[derive(Debug, MiniClap)]
+struct OptionalBool {
+    #[miniclap(short, long, flag_with_optional_value)]
+    force: bool,
+
+    pos: Vec<String>,
+}
+
+#[test]
+fn flag_with_optional_value() {
+    let opts = OptionalBool::try_parse_from(["test"]).unwrap();
+    assert!(!opts.force);
+
+    let opts = OptionalBool::try_parse_from(["test", "--force"]).unwrap();
+    assert!(opts.force);
+
+    let opts = OptionalBool::try_parse_from(["test", "--force", "true"]).unwrap();
+    assert!(opts.force);
+    assert!(opts.pos.is_empty());
+
+    let opts = OptionalBool::try_parse_from(["test", "--force", "false", "x"]).unwrap();
+    assert!(!opts.force);
+    assert_eq!(opts.pos, ["x"]);
+
+    let opts = OptionalBool::try_parse_from(["test", "-f", "positional"]).unwrap();
+    assert!(opts.force);
+    assert_eq!(opts.pos, ["positional"]);
+
+    let opts = OptionalBool::try_parse_from(["test", "--force=false", "true"]).unwrap();
+    assert!(!opts.force);
+    assert_eq!(opts.pos, ["true"]);
+
+    let opts = OptionalBool::try_parse_from(["test", "--force", "--", "false"]).unwrap();
+    assert!(opts.force);
+    assert_eq!(opts.pos, ["false"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct DefaultedFlags {
+    #[miniclap(long, default_value = false)]
+    dry_run: bool,
+
+    #[miniclap(long, default_value = true)]
+    color: bool,
+}
+
+#[test]
+fn flag_default_value() {
+    let opts = DefaultedFlags::try_parse_from(["test"]).unwrap();
+    assert_eq!((opts.dry_run, opts.color), (false, true));
+
+    let opts = DefaultedFlags::try_parse_from(["test", "--dry_run", "--color"]).unwrap();
+    assert_eq!((opts.dry_run, opts.color), (true, true));
+
+    let opts =
+        DefaultedFlags::try_parse_from(["test", "--dry_run=false", "--color=false"]).unwrap();
+    assert_eq!((opts.dry_run, opts.color), (false, false));
+
+    let opts = DefaultedFlags::try_parse_from(["test", "--color", "false"]).unwrap();
+    assert!(!opts.color);
+}
+
+#[derive(Debug, MiniClap)]
+struct MissingValue {
+    #[miniclap(short, long, default_value = 1, default_missing_value = "3")]
+    level: u8,
+
+    #[miniclap(long, default_missing_value = "always")]
+    color: Option<String>,
+
+    pos: Vec<String>,
+}
+
+#[test]
+fn default_missing_value() {
+    // Absent: uses `default_value`, or stays unset.
+    let opts = MissingValue::try_parse_from(["test"]).unwrap();
+    assert_eq!(opts.level, 1);
+    assert_eq!(opts.color, None);
+
+    // Present without a value: uses `default_missing_value`.
+    let opts = MissingValue::try_parse_from(["test", "--level", "--color"]).unwrap();
+    assert_eq!(opts.level, 3);
+    assert_eq!(opts.color.as_deref(), Some("always"));
+
+    // Present with a value: uses the value.
+    let opts = MissingValue::try_parse_from(["test", "--level=5", "-l7", "--color=never"]).unwrap();
+    assert_eq!(opts.level, 7);
+    assert_eq!(opts.color.as_deref(), Some("never"));
+
+    // A value must be attached, so the next argument is left alone.
+    let opts = MissingValue::try_parse_from(["test", "--color", "never"]).unwrap();
+    assert_eq!(opts.color.as_deref(), Some("always"));
+    assert_eq!(opts.pos, ["never"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct Numeric {
+    #[miniclap(long)]
+    big: Option<i128>,
+
+    #[miniclap(long, default_value = 0)]
+    huge: u128,
+
+    #[miniclap(long)]
+    jobs: std::num::NonZeroU32,
+
+    #[miniclap(long)]
+    retries: Option<std::num::NonZeroU8>,
+
+    #[miniclap(long)]
+    ids: Vec<std::num::NonZeroU64>,
+
+    pos: Option<u128>,
+}
+
+#[test]
+fn wide_and_non_zero_types() {
+    let opts = Numeric::try_parse_from([
+        "test",
+        "--big=-170141183460469231731687303715884105728",
+        "--huge=340282366920938463463374607431768211455",
+        "--jobs=4",
+        "--retries=2",
+        "--ids=1",
+        "--ids=2",
+        "7",
+    ])
+    .unwrap();
+    assert_eq!(opts.big, Some(i128::MIN));
+    assert_eq!(opts.huge, u128::MAX);
+    assert_eq!(opts.jobs.get(), 4);
+    assert_eq!(opts.retries.map(|x| x.get()), Some(2));
+    assert_eq!(opts.ids.iter().map(|x| x.get()).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(opts.pos, Some(7));
+
+    let opts = Numeric::try_parse_from(["test", "--jobs=1"]).unwrap();
+    assert_eq!(opts.big, None);
+    assert_eq!(opts.huge, 0);
+    assert_eq!(opts.retries, None);
+
+    let err = Numeric::try_parse_from(["test", "--jobs=0"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    let err = Numeric::try_parse_from(["test"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        miniclap::ErrorKind::MissingRequiredArgument
+    ));
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(matches)]
+struct Logging {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(long, default_value = 2)]
+    level: u8,
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(matches)]
+struct CommonOpts {
+    #[miniclap(long)]
+    config: String,
+
+    #[miniclap(flatten)]
+    logging: Logging,
+
+    input: Option<String>,
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(matches)]
+struct Deploy {
+    #[miniclap(short)]
+    force: bool,
+
+    #[miniclap(flatten)]
+    common: CommonOpts,
+
+    target: String,
+}
+
+#[test]
+fn flatten() {
+    let opts = Deploy::try_parse_from([
+        "test",
+        "-fv",
+        "prod",
+        "--config=deploy.toml",
+        "--level",
+        "4",
+        "in.txt",
+    ])
+    .unwrap();
+    assert!(opts.force);
+    assert_eq!(opts.target, "prod");
+    assert_eq!(opts.common.config, "deploy.toml");
+    assert_eq!(opts.common.input.as_deref(), Some("in.txt"));
+    assert!(opts.common.logging.verbose);
+    assert_eq!(opts.common.logging.level, 4);
+
+    // Flattened positionals come after the outer struct's own.
+    let opts = Deploy::try_parse_from(["test", "--config=c", "prod"]).unwrap();
+    assert_eq!(opts.target, "prod");
+    assert_eq!(opts.common.input, None);
+    assert!(!opts.common.logging.verbose);
+    assert_eq!(opts.common.logging.level, 2);
+
+    // Flattened arguments are validated like the outer ones.
+    let err = Deploy::try_parse_from(["test", "prod"]).unwrap_err();
+    assert_eq!(err.message, "Missing required argument '--config'");
+
+    let matches = Deploy::matches_from(["test", "-v", "--config=c", "prod"]).unwrap();
+    assert!(*matches.common().logging().verbose());
+}
+
+#[derive(Debug, MiniClap)]
+struct Tagged {
+    #[miniclap(long, clearable)]
+    tags: Vec<String>,
+
+    #[miniclap(short, clearable)]
+    ports: Vec<u16>,
+
+    #[miniclap(long)]
+    labels: Vec<String>,
+}
+
+#[test]
+fn clearable_vec() {
+    let opts =
+        Tagged::try_parse_from(["test", "--tags=a", "--tags=b", "--tags=", "--tags=c"]).unwrap();
+    assert_eq!(opts.tags, ["c"]);
+
+    let opts = Tagged::try_parse_from(["test", "-p80", "-p", "", "-p=443", "--tags", ""]).unwrap();
+    assert_eq!(opts.ports, [443]);
+    assert!(opts.tags.is_empty());
+
+    // Without `clearable`, an empty value is kept like any other.
+    let opts = Tagged::try_parse_from(["test", "--labels=a", "--labels="]).unwrap();
+    assert_eq!(opts.labels, ["a", ""]);
+}
+
+#[derive(Debug, MiniClap)]
+struct Timeouts {
+    #[miniclap(long)]
+    connect: std::time::Duration,
+
+    #[miniclap(long)]
+    idle: Option<std::time::Duration>,
+
+    #[miniclap(long)]
+    retry: Vec<std::time::Duration>,
+}
+
+#[test]
+fn duration_fields() {
+    use std::time::Duration;
+
+    let opts = Timeouts::try_parse_from([
+        "test",
+        "--connect=1500ms",
+        "--idle=2m",
+        "--retry=1s",
+        "--retry=1.5s",
+    ])
+    .unwrap();
+    assert_eq!(opts.connect, Duration::from_millis(1500));
+    assert_eq!(opts.idle, Some(Duration::from_secs(120)));
+    assert_eq!(
+        opts.retry,
+        [Duration::from_secs(1), Duration::from_millis(1500)]
+    );
+
+    let err = Timeouts::try_parse_from(["test", "--connect=5"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Argument 'connect' cannot parse '5': invalid duration '5', expected a number and unit \
+        such as '1500ms' or '2s'"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+struct Repl {
+    #[miniclap(long)]
+    num: u32,
+
+    #[miniclap(short)]
+    message: Option<String>,
+
+    words: Vec<String>,
+}
+
+#[test]
+fn parse_str() {
+    let opts = Repl::try_parse_str(r#"foo --num 10 -m "hello world" 'it''s' say\ \"hi\""#).unwrap();
+    assert_eq!(opts.num, 10);
+    assert_eq!(opts.message.as_deref(), Some("hello world"));
+    assert_eq!(opts.words, ["foo", "its", r#"say "hi""#]);
+
+    let err = Repl::try_parse_str("--num 10 -m 'oops").unwrap_err();
+    assert_eq!(err.message, "Unclosed single quote");
+}
+
+#[derive(Debug, MiniClap)]
+struct CopyFiles {
+    #[miniclap(short)]
+    recursive: bool,
+
+    dest: String,
+
+    #[miniclap(required)]
+    sources: Vec<String>,
+}
+
+#[test]
+fn required_vec_positional() {
+    let opts = CopyFiles::try_parse_from(["test", "-r", "out", "a", "b"]).unwrap();
+    assert!(opts.recursive);
+    assert_eq!(opts.dest, "out");
+    assert_eq!(opts.sources, ["a", "b"]);
+
+    let err = CopyFiles::try_parse_from(["test", "out"]).unwrap_err();
+    assert_eq!(err.message, "Missing required argument 'sources'");
+}
+
+#[derive(Debug, MiniClap)]
+struct Lossy {
+    #[miniclap(short)]
+    name: Option<String>,
+
+    #[miniclap(lossy)]
+    files: Vec<String>,
+}
+
+#[cfg(unix)]
+#[test]
+fn lossy_positional() {
+    use std::os::unix::ffi::OsStringExt;
+    let invalid = || OsString::from_vec(b"caf\xe9".to_vec());
+
+    let opts = Lossy::try_parse_from(vec!["test".into(), invalid(), "-nx".into()]).unwrap();
+    assert_eq!(opts.files, ["caf\u{fffd}"]);
+    assert_eq!(opts.name.as_deref(), Some("x"));
+
+    // Switches and their values must still be valid UTF-8.
+    let err = Lossy::try_parse_from(vec!["test".into(), "-n".into(), invalid()]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::InvalidUtf8));
+    let dash = OsString::from_vec(b"-\xe9".to_vec());
+    let err = Lossy::try_parse_from(vec!["test".into(), dash.clone()]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::InvalidUtf8));
+
+    let opts = Lossy::try_parse_from(vec!["test".into(), "--".into(), dash]).unwrap();
+    assert_eq!(opts.files, ["-\u{fffd}"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct RawPaths {
+    #[miniclap(long)]
+    force: bool,
+
+    #[miniclap(short)]
+    out: Option<std::path::PathBuf>,
+
+    input: std::path::PathBuf,
+
+    rest: Vec<OsString>,
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths() {
+    use std::os::unix::ffi::OsStringExt;
+    let invalid = |name: &[u8]| OsString::from_vec(name.to_vec());
+
+    let opts = RawPaths::try_parse_from(vec![
+        "test".into(),
+        "--force".into(),
+        invalid(b"caf\xe9"),
+        "-o".into(),
+        invalid(b"out\xff"),
+        "--".into(),
+        invalid(b"-\xe9"),
+    ])
+    .unwrap();
+    assert!(opts.force);
+    assert_eq!(opts.input.into_os_string(), invalid(b"caf\xe9"));
+    assert_eq!(opts.out.unwrap().into_os_string(), invalid(b"out\xff"));
+    assert_eq!(opts.rest, [invalid(b"-\xe9")]);
+
+    // Switches are still matched as text.
+    let err = RawPaths::try_parse_from(vec!["test".into(), invalid(b"--\xe9")]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::InvalidUtf8));
+}
+
+#[derive(Debug, MiniClap)]
+struct MixedPositionals {
+    name: String,
+
+    raw: OsString,
+}
+
+#[cfg(unix)]
+#[test]
+fn utf8_checked_per_positional() {
+    use std::os::unix::ffi::OsStringExt;
+    let invalid = |name: &[u8]| OsString::from_vec(name.to_vec());
+
+    let opts =
+        MixedPositionals::try_parse_from(vec!["test".into(), "café".into(), invalid(b"\xff")])
+            .unwrap();
+    assert_eq!(opts.name, "café");
+    assert_eq!(opts.raw, invalid(b"\xff"));
+
+    // Only the `String` positional rejects invalid UTF-8.
+    let err = MixedPositionals::try_parse_from(vec!["test".into(), invalid(b"\xff"), "ok".into()])
+        .unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::InvalidUtf8));
+}
+
+#[test]
+fn unknown_switch_suggestion() {
+    let err = Server::try_parse_from(["test", "--prot=80", "localhost"]).unwrap_err();
+    assert_eq!(err.message, "Did not recognize argument '--prot'");
+    assert_eq!(err.suggestion.as_deref(), Some("--port"));
+    assert_eq!(
+        err.to_string(),
+        "Did not recognize argument '--prot' (did you mean '--port'?)"
+    );
+
+    let err = Server::try_parse_from(["test", "--verbose", "localhost"]).unwrap_err();
+    assert_eq!(err.suggestion, None);
+}
+
+#[test]
+fn missing_switch_name() {
+    let err = Server::try_parse_from(["test", "-=5", "localhost"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::UnknownSwitch));
+    assert_eq!(
+        err.message,
+        "Argument '-=5' has no switch name before the '='"
+    );
+    let err = Server::try_parse_from(["test", "--=5", "localhost"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Argument '--=5' has no switch name before the '='"
+    );
+    let err = Server::try_parse_from(["test", "-="]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Argument '-=' has no switch name before the '='"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+struct Listen {
+    #[miniclap(long, default_value = 80, default_value = 443)]
+    ports: Vec<u16>,
+}
+
+#[test]
+fn multiple_default_values() {
+    let opts = Listen::try_parse_from(["test"]).unwrap();
+    assert_eq!(opts.ports, [80, 443]);
+
+    let opts = Listen::try_parse_from(["test", "--ports=8080"]).unwrap();
+    assert_eq!(opts.ports, [8080]);
+}
+
+#[derive(Debug, MiniClap)]
+struct Separator {
+    #[miniclap(short, long)]
+    sep: Option<String>,
+
+    rest: Vec<String>,
+}
+
+#[test]
+fn double_dash_as_value() {
+    let opts = Separator::try_parse_from(["test", "--sep=--", "--", "-a"]).unwrap();
+    assert_eq!(opts.sep.as_deref(), Some("--"));
+    assert_eq!(opts.rest, ["-a"]);
+
+    // A detached value is taken as is, so this `--` does not start trailing mode.
+    let opts = Separator::try_parse_from(["test", "--sep", "--", "-s", "-x"]).unwrap();
+    assert_eq!(opts.sep.as_deref(), Some("-x"));
+    assert!(opts.rest.is_empty());
+
+    let opts = Separator::try_parse_from(["test", "-s", "--", "--", "--sep"]).unwrap();
+    assert_eq!(opts.sep.as_deref(), Some("--"));
+    assert_eq!(opts.rest, ["--sep"]);
+}
+
+#[derive(Debug, PartialEq, MiniClap)]
+#[miniclap(derive_default)]
+struct Config {
+    #[miniclap(long, default_value = 8080)]
+    port: u16,
+
+    #[miniclap(long, default_value = 1, default_value = 2)]
+    levels: Vec<u8>,
+
+    #[miniclap(short)]
+    verbose: bool,
+
+    #[miniclap(long)]
+    name: Option<String>,
+
+    path: String,
+}
+
+#[test]
+fn derive_default() {
+    assert_eq!(
+        Config::default(),
+        Config {
+            port: 8080,
+            levels: vec![1, 2],
+            verbose: false,
+            name: None,
+            path: String::new(),
+        }
+    );
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(env_prefix = "MINICLAP_TEST_DEPLOY")]
+struct EnvDeploy {
+    #[miniclap(long)]
+    region: String,
+
+    #[miniclap(long, default_value = 1)]
+    replicas: u32,
+
+    #[miniclap(long)]
+    tags: Vec<String>,
+
+    #[miniclap(long)]
+    dry_run: bool,
+
+    #[miniclap(short)]
+    force: bool,
+
+    #[miniclap(long)]
+    r#type: Option<String>,
+
+    target: Option<String>,
+}
+
+#[test]
+fn env_prefix() {
+    std::env::set_var("MINICLAP_TEST_DEPLOY_REGION", "eu");
+    std::env::set_var("MINICLAP_TEST_DEPLOY_REPLICAS", "3");
+    std::env::set_var("MINICLAP_TEST_DEPLOY_TAGS", "env");
+    std::env::set_var("MINICLAP_TEST_DEPLOY_DRY_RUN", "yes");
+    std::env::set_var("MINICLAP_TEST_DEPLOY_TYPE", "canary");
+    // Only arguments with a long switch read the environment.
+    std::env::set_var("MINICLAP_TEST_DEPLOY_FORCE", "1");
+    std::env::set_var("MINICLAP_TEST_DEPLOY_TARGET", "prod");
+
+    let opts = EnvDeploy::try_parse_from(["test"]).unwrap();
+    assert_eq!(opts.region, "eu");
+    assert_eq!(opts.replicas, 3);
+    assert_eq!(opts.tags, ["env"]);
+    assert!(opts.dry_run);
+    assert!(!opts.force);
+    assert_eq!(opts.r#type.as_deref(), Some("canary"));
+    assert_eq!(opts.target, None);
+
+    // The command line takes precedence, and replaces rather than adds to a `Vec`.
+    let opts = EnvDeploy::try_parse_from(["test", "--region=us", "--tags=a", "--tags=b"]).unwrap();
+    assert_eq!(opts.region, "us");
+    assert_eq!(opts.tags, ["a", "b"]);
+    let opts = EnvDeploy::try_parse_from(["test", "--type", "blue"]).unwrap();
+    assert_eq!(opts.r#type.as_deref(), Some("blue"));
+
+    std::env::set_var("MINICLAP_TEST_DEPLOY_REPLICAS", "many");
+    let err = EnvDeploy::try_parse_from(["test"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    std::env::remove_var("MINICLAP_TEST_DEPLOY_REPLICAS");
+
+    std::env::set_var("MINICLAP_TEST_DEPLOY_DRY_RUN", "0");
+    std::env::remove_var("MINICLAP_TEST_DEPLOY_REGION");
+    let opts = EnvDeploy::try_parse_from(["test", "--region=ap"]).unwrap();
+    assert_eq!(opts.replicas, 1);
+    assert!(!opts.dry_run);
+}
+
+#[derive(Debug, MiniClap)]
+struct Counted {
+    #[miniclap(short, long, count)]
+    verbose: u8,
+
+    #[miniclap(short, count, max = 2)]
+    quiet: u32,
+}
+
+#[test]
+fn count_flags() {
+    let opts = Counted::try_parse_from(["test"]).unwrap();
+    assert_eq!((opts.verbose, opts.quiet), (0, 0));
+
+    let opts = Counted::try_parse_from(["test", "-vv", "--verbose", "-qqqq"]).unwrap();
+    assert_eq!((opts.verbose, opts.quiet), (3, 2));
+
+    let many = format!("-{}", "v".repeat(300));
+    let opts = Counted::try_parse_from(["test", many.as_str()]).unwrap();
+    assert_eq!(opts.verbose, 255);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, miniclap::CountLevel)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(derive_default, to_args, matches)]
+struct Leveled {
+    #[miniclap(short = 'v', long = "verbose", count)]
+    level: LogLevel,
+}
+
+#[test]
+fn count_levels() {
+    let level = |args: &[&str]| {
+        let args = std::iter::once("test").chain(args.iter().copied());
+        Leveled::try_parse_from(args).unwrap().level
+    };
+    assert_eq!(level(&[]), LogLevel::Error);
+    assert_eq!(level(&["-v"]), LogLevel::Warn);
+    assert_eq!(level(&["-vv"]), LogLevel::Info);
+    assert_eq!(level(&["-vv", "--verbose"]), LogLevel::Debug);
+    assert_eq!(level(&["-vvvv"]), LogLevel::Trace);
+    assert_eq!(level(&["-vvvvvvvvvv"]), LogLevel::Trace);
+
+    assert_eq!(Leveled::default().level, LogLevel::Error);
+    let opts = Leveled::try_parse_from(["test", "-vv"]).unwrap();
+    assert_eq!(opts.to_args(), ["--verbose", "--verbose"]);
+    let matches = Leveled::matches_from(["test", "-vvvvvv"]).unwrap();
+    assert_eq!(*matches.level(), 6);
+}
+
+#[derive(Debug, MiniClap)]
+struct Spellings {
+    #[miniclap(short, long)]
+    tag: Vec<String>,
+
+    #[miniclap(short)]
+    all: bool,
+}
+
+#[test]
+fn multi_option_spellings() {
+    let opts = Spellings::try_parse_from([
+        "test", "--tag", "a", "--tag=b", "-t", "c", "-td", "-t=e", "-at", "f", "-atg",
+    ])
+    .unwrap();
+    assert!(opts.all);
+    assert_eq!(opts.tag, ["a", "b", "c", "d", "e", "f", "g"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct Network {
+    #[miniclap(long)]
+    ip: Option<std::net::IpAddr>,
+
+    #[miniclap(long)]
+    v4: Option<std::net::Ipv4Addr>,
+
+    #[miniclap(long)]
+    listen: Vec<std::net::SocketAddr>,
+}
+
+#[test]
+fn network_types() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    let opts = Network::try_parse_from([
+        "test",
+        "--ip=::1",
+        "--v4=10.0.0.1",
+        "--listen=127.0.0.1:80",
+        "--listen=[::1]:443",
+    ])
+    .unwrap();
+    assert_eq!(opts.ip, Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    assert_eq!(opts.v4, Some(Ipv4Addr::new(10, 0, 0, 1)));
+    assert_eq!(
+        opts.listen,
+        [
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 80)),
+            SocketAddr::from((Ipv6Addr::LOCALHOST, 443)),
+        ]
+    );
+
+    let err = Network::try_parse_from(["test", "--ip=300.1.1.1"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    assert_eq!(
+        err.message,
+        "Invalid IP address '300.1.1.1' for argument 'ip' (expected something like 127.0.0.1 or ::1)"
+    );
+    let err = Network::try_parse_from(["test", "--v4=::1"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Invalid IPv4 address '::1' for argument 'v4' (expected something like 127.0.0.1)"
+    );
+    let err = Network::try_parse_from(["test", "--listen=127.0.0.1"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Invalid socket address '127.0.0.1' for argument 'listen' (expected something like \
+        127.0.0.1:8080 or [::1]:8080)"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(unknown_as_positional)]
+struct Passthrough {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    passthrough: Vec<String>,
+}
+
+#[test]
+fn unknown_as_positional() {
+    let opts = Passthrough::try_parse_from([
+        "test",
+        "--unknown",
+        "val",
+        "-v",
+        "--color=auto",
+        "-xv",
+        "--",
+        "--verbose",
+    ])
+    .unwrap();
+    assert!(opts.verbose);
+    assert_eq!(
+        opts.passthrough,
+        ["--unknown", "val", "--color=auto", "-xv", "--verbose"]
+    );
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(allow_negative_numbers)]
+struct Offsets {
+    #[miniclap(short = '1')]
+    once: bool,
+
+    #[miniclap(short)]
+    scale: Option<f64>,
+
+    offsets: Vec<f64>,
+}
+
+#[derive(Debug, MiniClap)]
+struct Offset {
+    offset: i32,
+}
+
+#[test]
+fn allow_negative_numbers() {
+    let opts = Offsets::try_parse_from(["test", "-5", "2", "-2.5", "-s", "-.5", "-.25"]).unwrap();
+    assert!(!opts.once);
+    assert_eq!(opts.scale, Some(-0.5));
+    assert_eq!(opts.offsets, [-5.0, 2.0, -2.5, -0.25]);
+
+    // `-1` is a switch, so it is not taken as a number.
+    let err = Offsets::try_parse_from(["test", "-1.5"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::UnknownSwitch));
+    assert!(Offsets::try_parse_from(["test", "-x5"]).is_err());
+
+    let err = Offset::try_parse_from(["test", "-5"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::UnknownSwitch));
+    assert_eq!(
+        Offset::try_parse_from(["test", "--", "-5"]).unwrap().offset,
+        -5
+    );
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(allow_space_around_equals)]
+struct SpacedEquals {
+    #[miniclap(short, long)]
+    name: Option<String>,
+
+    rest: Vec<String>,
+}
+
+#[derive(Debug, MiniClap)]
+struct UnspacedEquals {
+    #[miniclap(long)]
+    name: Option<String>,
+
+    rest: Vec<String>,
+}
+
+#[test]
+fn allow_space_around_equals() {
+    let opts = SpacedEquals::try_parse_from(["test", "--name", "=", "x", "="]).unwrap();
+    assert_eq!(opts.name.as_deref(), Some("x"));
+    assert_eq!(opts.rest, ["="]);
+    let opts = SpacedEquals::try_parse_from(["test", "-n", "=", "="]).unwrap();
+    assert_eq!(opts.name.as_deref(), Some("="));
+    let opts = SpacedEquals::try_parse_from(["test", "--name", "y"]).unwrap();
+    assert_eq!(opts.name.as_deref(), Some("y"));
+    let opts = SpacedEquals::try_parse_from(["test", "--name==", "z"]).unwrap();
+    assert_eq!(opts.name.as_deref(), Some("="));
+    assert_eq!(opts.rest, ["z"]);
+
+    let err = SpacedEquals::try_parse_from(["test", "--name", "="]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::MissingValue));
+
+    // Off by default, when `=` is just the value.
+    let opts = UnspacedEquals::try_parse_from(["test", "--name", "=", "x"]).unwrap();
+    assert_eq!(opts.name.as_deref(), Some("="));
+    assert_eq!(opts.rest, ["x"]);
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(ignore_extra_positionals)]
+struct Lenient {
+    #[miniclap(short)]
+    force: bool,
+
+    source: String,
+}
+
+#[test]
+fn ignore_extra_positionals() {
+    let res = Lenient::try_parse_from_with_warnings(["test", "a", "b", "-f", "c"]).unwrap();
+    assert_eq!(res.value.source, "a");
+    assert!(res.value.force);
+    assert_eq!(
+        res.warnings,
+        [
+            "Ignored extra positional argument 'b'",
+            "Ignored extra positional argument 'c'"
+        ]
+    );
+
+    // Still an error by default.
+    let err = Offset::try_parse_from(["test", "1", "2"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::TooManyPositional));
+}
+
+#[derive(Debug, MiniClap)]
+struct Filters {
+    #[miniclap(short = 'x')]
+    exclude: Vec<String>,
+}
+
+#[derive(Debug, MiniClap)]
+struct Archive {
+    #[miniclap(short = 'x')]
+    extract: bool,
+
+    #[miniclap(short)]
+    verbose: bool,
+
+    #[miniclap(flatten)]
+    filters: Filters,
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(prefer_short_options)]
+struct ArchiveFilter {
+    #[miniclap(short = 'x')]
+    extract: bool,
+
+    #[miniclap(short)]
+    verbose: bool,
+
+    #[miniclap(flatten)]
+    filters: Filters,
+}
+
+#[test]
+fn prefer_short_options() {
+    // `-x` is both the outer flag and the flattened option, and is taken as the flag.
+    let opts = Archive::try_parse_from(["test", "-xv"]).unwrap();
+    assert!(opts.extract);
+    assert!(opts.verbose);
+    assert!(opts.filters.exclude.is_empty());
+
+    // With `prefer_short_options`, it is taken as the option instead.
+    let opts = ArchiveFilter::try_parse_from(["test", "-xv", "-vx", "tmp"]).unwrap();
+    assert!(!opts.extract);
+    assert!(opts.verbose);
+    assert_eq!(opts.filters.exclude, ["v", "tmp"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct Bases {
+    #[miniclap(long, number_base = 16)]
+    mask: u32,
+
+    #[miniclap(long, number_base = 8)]
+    mode: Option<u16>,
+
+    #[miniclap(long, number_base = 16)]
+    bytes: Vec<u8>,
+}
+
+#[test]
+fn number_base() {
+    let opts = Bases::try_parse_from([
+        "test",
+        "--mask",
+        "ff",
+        "--mode=0o755",
+        "--bytes",
+        "0x0A",
+        "--bytes",
+        "Ff",
+    ])
+    .unwrap();
+    assert_eq!(opts.mask, 0xff);
+    assert_eq!(opts.mode, Some(0o755));
+    assert_eq!(opts.bytes, [0x0a, 0xff]);
+
+    let opts = Bases::try_parse_from(["test", "--mask=0xff", "--mode", "644"]).unwrap();
+    assert_eq!(opts.mask, 0xff);
+    assert_eq!(opts.mode, Some(0o644));
+
+    let err = Bases::try_parse_from(["test", "--mask", "fg"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    let err = Bases::try_parse_from(["test", "--mask=0", "--mode", "9"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Argument 'mode' cannot parse '9': invalid digit found in string"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+struct Plugins {
+    #[miniclap(long, exclusive)]
+    list_plugins: bool,
+
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(long, default_value = 4)]
+    jobs: u32,
+
+    plugin: String,
+}
+
+#[test]
+fn exclusive_flag() {
+    let opts = Plugins::try_parse_from(["test", "--list_plugins"]).unwrap();
+    assert!(opts.list_plugins);
+    assert!(!opts.verbose);
+    assert_eq!(opts.jobs, 4);
+    assert_eq!(opts.plugin, "");
+
+    let opts = Plugins::try_parse_from(["test", "-v", "--jobs=2", "foo"]).unwrap();
+    assert!(!opts.list_plugins);
+    assert_eq!((opts.verbose, opts.jobs), (true, 2));
+    assert_eq!(opts.plugin, "foo");
+
+    for argv in [
+        &["test", "--list_plugins", "-v"][..],
+        &["test", "foo", "--list_plugins"],
+        &["test", "--list_plugins", "--jobs", "2"],
+    ] {
+        let err = Plugins::try_parse_from(argv).unwrap_err();
+        assert!(matches!(err.kind, miniclap::ErrorKind::ArgumentConflict));
+        assert_eq!(
+            err.message,
+            "'--list_plugins' cannot be used with other arguments"
+        );
+    }
+}
+
+#[derive(Debug, MiniClap)]
+enum PluginCommand {
+    Install { name: String },
+}
+
+#[derive(Debug, MiniClap)]
+struct PluginCli {
+    #[miniclap(long, exclusive)]
+    list_plugins: bool,
+
+    #[miniclap(flatten)]
+    common: CommonOpts,
+
+    #[miniclap(subcommand)]
+    command: Option<PluginCommand>,
+}
+
+#[test]
+fn exclusive_flag_with_subcommand() {
+    // Neither the subcommand nor the flattened struct has a default, so they are built from what
+    // was collected, which is nothing.
+    let opts = PluginCli::try_parse_from(["test", "--list_plugins"]).unwrap();
+    assert!(opts.list_plugins);
+    assert!(opts.command.is_none());
+    assert_eq!(opts.common.config, "");
+    assert_eq!(opts.common.logging.level, 2);
+    assert_eq!(opts.common.input, None);
+
+    let argv = ["test", "--config=a.toml", "in.txt", "install", "foo"];
+    let opts = PluginCli::try_parse_from(argv).unwrap();
+    assert!(!opts.list_plugins);
+    assert_eq!(opts.common.input.as_deref(), Some("in.txt"));
+    assert!(matches!(opts.command, Some(PluginCommand::Install { name }) if name == "foo"));
+
+    let argv = ["test", "--list_plugins", "in.txt", "install", "foo"];
+    let err = PluginCli::try_parse_from(argv).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ArgumentConflict));
+
+    let opts = Listener::try_parse_from(["test", "--listen=127.0.0.1:80"]).unwrap();
+    assert!(!opts.list_plugins);
+    assert_eq!(opts.opts.listen.port(), 80);
+
+    // A required argument of a flattened struct without a default is still reported missing.
+    let err = Listener::try_parse_from(["test", "--list_plugins"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        miniclap::ErrorKind::MissingRequiredArgument
+    ));
+}
+
+#[derive(Debug, MiniClap)]
+struct ListenOpts {
+    #[miniclap(long)]
+    listen: std::net::SocketAddr,
+}
+
+#[derive(Debug, MiniClap)]
+struct Listener {
+    #[miniclap(long, exclusive)]
+    list_plugins: bool,
+
+    #[miniclap(flatten)]
+    opts: ListenOpts,
+}
+
+#[derive(Debug, MiniClap)]
+struct Delimited {
+    #[miniclap(long, value_delimiters = ",")]
+    ids: Vec<u32>,
+
+    #[miniclap(long, value_delimiters = " ")]
+    words: Vec<String>,
+
+    #[miniclap(long, value_delimiters = ", ")]
+    tags: Vec<String>,
+}
+
+#[test]
+fn value_delimiters() {
+    let opts = Delimited::try_parse_from([
+        "test",
+        "--ids",
+        "1,2",
+        "--ids=3,,4,",
+        "--words",
+        " a  b",
+        "--tags",
+        "x, y z,,",
+        "--tags=w",
+    ])
+    .unwrap();
+    assert_eq!(opts.ids, [1, 2, 3, 4]);
+    assert_eq!(opts.words, ["a", "b"]);
+    assert_eq!(opts.tags, ["x", "y", "z", "w"]);
+
+    // A space is only a delimiter when listed.
+    let err = Delimited::try_parse_from(["test", "--ids", "1, 2"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Argument 'ids' cannot parse ' 2': invalid digit found in string"
+    );
+    let opts = Delimited::try_parse_from(["test", "--ids", ",,"]).unwrap();
+    assert!(opts.ids.is_empty());
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(max_args = 3)]
+struct Capped {
+    #[miniclap(short, long)]
+    level: Option<u8>,
+
+    files: Vec<String>,
+}
+
+#[test]
+fn max_args() {
+    let opts = Capped::try_parse_from(["test", "-l", "2", "a"]).unwrap();
+    assert_eq!(opts.level, Some(2));
+    assert_eq!(opts.files, ["a"]);
+
+    let many = std::iter::once("test".to_string()).chain((0..1000).map(|i| i.to_string()));
+    let err = Capped::try_parse_from(many).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::Other));
+    assert_eq!(err.message, "Too many arguments (expected at most 3)");
+
+    // A value taken by the last switch counts too.
+    let err = Capped::try_parse_from(["test", "a", "b", "-l", "2"]).unwrap_err();
+    assert_eq!(err.message, "Too many arguments (expected at most 3)");
+
+    // A raw option stops taking arguments once over the cap.
+    let opts = CappedExec::try_parse_from(["test", "--exec", "a", "b"]).unwrap();
+    assert_eq!(opts.exec, ["a", "b"]);
+    let pulled = std::cell::Cell::new(0);
+    let many = ["test", "--exec"].iter().map(|x| x.to_string()).chain(
+        (0..1000)
+            .map(|i| i.to_string())
+            .inspect(|_| pulled.set(pulled.get() + 1)),
+    );
+    let err = CappedExec::try_parse_from(many).unwrap_err();
+    assert_eq!(err.message, "Too many arguments (expected at most 3)");
+    assert_eq!(pulled.get(), 3);
+
+    // So does a subcommand, which counts against the cap of the struct containing it.
+    let opts = CappedRun::try_parse_from(["test", "run", "a", "b"]).unwrap();
+    assert!(matches!(opts.command, CappedCommand::Run { args } if args == ["a", "b"]));
+    let err = CappedRun::try_parse_from(["test", "run", "a", "b", "c"]).unwrap_err();
+    assert_eq!(err.message, "Too many arguments (expected at most 3)");
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(max_args = 3)]
+struct CappedExec {
+    #[miniclap(long, raw)]
+    exec: Vec<String>,
+}
+
+#[derive(Debug, MiniClap)]
+enum CappedCommand {
+    Run { args: Vec<String> },
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(max_args = 3)]
+struct CappedRun {
+    #[miniclap(subcommand)]
+    command: CappedCommand,
+}
+
+#[derive(Debug, MiniClap)]
+struct ConditionalDefaults {
+    #[miniclap(long)]
+    tls: bool,
+
+    #[miniclap(long)]
+    mode: Option<String>,
+
+    #[miniclap(long, default_value = 80, default_value_if(tls, 443))]
+    port: u16,
+
+    #[miniclap(long, default_value_if(mode = "debug", 3), default_value_if(tls, 1))]
+    level: Option<u8>,
+}
+
+#[test]
+fn default_value_if() {
+    let opts = ConditionalDefaults::try_parse_from(["test"]).unwrap();
+    assert_eq!((opts.port, opts.level), (80, None));
+
+    let opts = ConditionalDefaults::try_parse_from(["test", "--tls"]).unwrap();
+    assert!(opts.tls);
+    assert_eq!((opts.port, opts.level), (443, Some(1)));
+
+    let opts = ConditionalDefaults::try_parse_from(["test", "--tls", "--port=8443"]).unwrap();
+    assert_eq!(opts.port, 8443);
+
+    // The first condition that holds wins.
+    let opts = ConditionalDefaults::try_parse_from(["test", "--mode=debug", "--tls"]).unwrap();
+    assert_eq!(opts.level, Some(3));
+
+    let opts = ConditionalDefaults::try_parse_from(["test", "--mode=release"]).unwrap();
+    assert_eq!(opts.mode.as_deref(), Some("release"));
+    assert_eq!(opts.level, None);
+}
+
+#[test]
+fn spec() {
+    use miniclap::{ArgInfo, ArgKind, Switch};
+
+    const SPEC: &[ArgInfo] = Capped::SPEC;
+    assert_eq!(
+        SPEC,
+        [
+            ArgInfo {
+                name: "level",
+                kind: ArgKind::Option,
+                switch: Some(Switch::Both('l', "level")),
+                is_required: false,
+                is_multiple: false,
+                env: None,
+                type_name: "Option<u8>",
+                default_values: &[],
+            },
+            ArgInfo {
+                name: "files",
+                kind: ArgKind::Positional,
+                switch: None,
+                is_required: false,
+                is_multiple: true,
+                env: None,
+                type_name: "Vec<String>",
+                default_values: &[],
+            },
+        ]
+    );
+    let kinds: Vec<_> = Plugins::SPEC
+        .iter()
+        .map(|x| (x.name, x.kind, x.is_required))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("list_plugins", ArgKind::Flag, false),
+            ("verbose", ArgKind::Flag, false),
+            ("jobs", ArgKind::Option, false),
+            ("plugin", ArgKind::Positional, true),
+        ]
+    );
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(env_prefix = "MINICLAP_SCHEMA")]
+struct Release {
+    #[miniclap(short, long, default_value = 3)]
+    retries: u32,
+
+    #[miniclap(long)]
+    tag: Vec<String>,
+
+    target: String,
+
+    hosts: Vec<String>,
+}
+
+#[test]
+fn generate_schema() {
+    let schema = miniclap::generate_schema(Release::SPEC);
+    assert_eq!(
+        schema,
+        r#"{
+  "arguments": [
+    {"name": "retries", "kind": "option", "short": "r", "long": "retries", "type": "u32", "required": false, "multiple": false, "default": ["3"], "env": "MINICLAP_SCHEMA_RETRIES"},
+    {"name": "tag", "kind": "option", "short": null, "long": "tag", "type": "Vec<String>", "required": false, "multiple": true, "default": [], "env": "MINICLAP_SCHEMA_TAG"},
+    {"name": "target", "kind": "positional", "short": null, "long": null, "type": "String", "required": true, "multiple": false, "default": [], "env": null},
+    {"name": "hosts", "kind": "positional", "short": null, "long": null, "type": "Vec<String>", "required": false, "multiple": true, "default": [], "env": null}
+  ]
+}
+"#
+    );
+    assert_eq!(
+        miniclap::generate_schema(&[]),
+        "{\n  \"arguments\": []\n}\n"
+    );
+
+    let opts = Release::try_parse_from(["test", "--tag", "v1", "prod", "a", "b"]).unwrap();
+    assert_eq!(opts.retries, 3);
+    assert_eq!(opts.tag, ["v1"]);
+    assert_eq!(opts.target, "prod");
+    assert_eq!(opts.hosts, ["a", "b"]);
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(group(format, required), group(source, multiple))]
+struct Export {
+    #[miniclap(long, group = "format")]
+    json: bool,
+
+    #[miniclap(long, group = "format")]
+    yaml: bool,
+
+    #[miniclap(long, group = "format")]
+    template: Option<String>,
+
+    #[miniclap(long, group = "source")]
+    local: bool,
+
+    #[miniclap(long, group = "source")]
+    remote: Option<String>,
+
+    #[miniclap(long, group = "target")]
+    stdout: bool,
+
+    #[miniclap(long, group = "target")]
+    file: Option<String>,
+}
+
+#[test]
+fn groups() {
+    let opts = Export::try_parse_from(["test", "--json", "--local", "--remote=x"]).unwrap();
+    assert!(opts.json && !opts.yaml && opts.template.is_none());
+    assert!(opts.local);
+    assert_eq!(opts.remote.as_deref(), Some("x"));
+    assert!(!opts.stdout && opts.file.is_none());
+
+    let opts = Export::try_parse_from(["test", "--template=t", "--file=out"]).unwrap();
+    assert_eq!(opts.template.as_deref(), Some("t"));
+    assert_eq!(opts.file.as_deref(), Some("out"));
+
+    let err = Export::try_parse_from(["test", "--local"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        miniclap::ErrorKind::MissingRequiredArgument
+    ));
+    assert_eq!(
+        err.message,
+        "One of 'json', 'yaml', 'template' is required (group 'format')"
+    );
+
+    let err = Export::try_parse_from(["test", "--json", "--template=t"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ArgumentConflict));
+    assert_eq!(
+        err.message,
+        "Only one of 'json', 'yaml', 'template' may be given (group 'format')"
+    );
+
+    let err = Export::try_parse_from(["test", "--yaml", "--stdout", "--file=x"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ArgumentConflict));
+    assert_eq!(
+        err.message,
+        "Only one of 'stdout', 'file' may be given (group 'target')"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+struct RequiredSwitches {
+    #[miniclap(short, long)]
+    num: u32,
+
+    #[miniclap(short)]
+    jobs: u32,
+
+    #[miniclap(long, required)]
+    include: Vec<String>,
+}
+
+#[test]
+fn missing_required_names_the_switch() {
+    let err = RequiredSwitches::try_parse_from(["test", "-j", "2"]).unwrap_err();
+    assert_eq!(err.message, "Missing required argument '--num'");
+    let err = RequiredSwitches::try_parse_from(["test", "-n", "1"]).unwrap_err();
+    assert_eq!(err.message, "Missing required argument '-j'");
+    let err = RequiredSwitches::try_parse_from(["test", "-n", "1", "-j", "2"]).unwrap_err();
+    assert_eq!(err.message, "Missing required argument '--include'");
+
+    let opts = RequiredSwitches::try_parse_from(["test", "-n1", "-j2", "--include=a"]).unwrap();
+    assert_eq!((opts.num, opts.jobs), (1, 2));
+    assert_eq!(opts.include, ["a"]);
+}
+
+#[derive(Debug, MiniClap)]
+struct TriState {
+    #[miniclap(short, long)]
+    color: Option<bool>,
+
+    #[miniclap(long)]
+    pager: Option<bool>,
+}
+
+#[test]
+fn option_bool_flag() {
+    let opts = TriState::try_parse_from(["test"]).unwrap();
+    assert_eq!((opts.color, opts.pager), (None, None));
+
+    let opts = TriState::try_parse_from(["test", "-c", "--no-pager"]).unwrap();
+    assert_eq!((opts.color, opts.pager), (Some(true), Some(false)));
+
+    // The last of the two switches wins.
+    let opts = TriState::try_parse_from(["test", "--no-color", "--color", "--pager", "--no-pager"])
+        .unwrap();
+    assert_eq!((opts.color, opts.pager), (Some(true), Some(false)));
+
+    let err = TriState::try_parse_from(["test", "--color=false"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::UnexpectedValue));
+}
+
+#[derive(Debug, MiniClap)]
+struct RequireEquals {
+    #[miniclap(short, long, require_equals)]
+    opt: Option<String>,
+
+    #[miniclap(long, flag_with_optional_value, require_equals)]
+    force: bool,
+
+    rest: Vec<String>,
+}
+
+#[test]
+fn require_equals() {
+    let opts = RequireEquals::try_parse_from(["test", "--opt=val", "--force", "false"]).unwrap();
+    assert_eq!(opts.opt.as_deref(), Some("val"));
+    assert!(opts.force);
+    assert_eq!(opts.rest, ["false"]);
+
+    let opts = RequireEquals::try_parse_from(["test", "-oval", "--force=false"]).unwrap();
+    assert_eq!(opts.opt.as_deref(), Some("val"));
+    assert!(!opts.force);
+
+    let err = RequireEquals::try_parse_from(["test", "--opt", "val"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::MissingValue));
+    assert_eq!(
+        err.message,
+        "Missing value for '--opt', which must be attached as in '--opt=value'"
+    );
+    let err = RequireEquals::try_parse_from(["test", "-o", "val"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Missing value for '-o', which must be attached as in '-ovalue'"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+struct PortRange {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    ports: [u16; 2],
+
+    host: Option<String>,
+}
+
+#[test]
+fn array_positional() {
+    let opts = PortRange::try_parse_from(["test", "80", "-v", "8080", "localhost"]).unwrap();
+    assert_eq!(opts.ports, [80, 8080]);
+    assert!(opts.verbose);
+    assert_eq!(opts.host.as_deref(), Some("localhost"));
+
+    let err = PortRange::try_parse_from(["test", "80"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::MissingValue));
+    assert_eq!(err.message, "Argument 'ports' expects 2 values, got 1");
+
+    let err = PortRange::try_parse_from(["test", "80", "8080", "localhost", "x"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::TooManyPositional));
+
+    // Each element is parsed on its own, and a bad one is reported like any other value.
+    let err = PortRange::try_parse_from(["test", "80", "http"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    assert!(err
+        .message
+        .starts_with("Argument 'ports' cannot parse 'http'"));
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(reserved_shorts = "hV")]
+struct Reserved {
+    #[miniclap(long)]
+    host: Option<String>,
+
+    #[miniclap(short, long)]
+    verbose: bool,
+}
+
+#[test]
+fn reserved_shorts() {
+    let opts = Reserved::try_parse_from(["test", "--host", "x", "-v"]).unwrap();
+    assert_eq!(opts.host.as_deref(), Some("x"));
+    assert!(opts.verbose);
+    assert!(Reserved::try_parse_from(["test", "-h"]).is_err());
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(unknown_as_positional)]
+struct Deprecations {
+    #[miniclap(long, deprecated = "use --jobs instead")]
+    threads: Option<u32>,
+
+    #[miniclap(short, deprecated)]
+    quiet: bool,
+
+    #[miniclap(long)]
+    jobs: Option<u32>,
+
+    rest: Vec<String>,
+}
+
+#[test]
+fn warnings_in_result() {
+    let res = Deprecations::try_parse_from_with_warnings(["test", "--threads", "4", "-q", "--x"])
+        .unwrap();
+    assert_eq!(res.value.threads, Some(4));
+    assert!(res.value.quiet);
+    assert_eq!(res.value.rest, ["--x"]);
+    assert_eq!(
+        res.warnings,
+        [
+            "Took unknown argument '--x' as a positional argument",
+            "Argument '--threads' is deprecated: use --jobs instead",
+            "Argument '-q' is deprecated",
+        ]
+    );
+
+    let res = Deprecations::try_parse_from_with_warnings(["test", "--jobs", "4"]).unwrap();
+    assert_eq!(res.value.jobs, Some(4));
+    assert!(res.warnings.is_empty());
+}
+
+fn strip_comment(value: String) -> String {
+    match value.find('#') {
+        Some(i) => value[..i].trim_end().to_string(),
+        None => value,
+    }
+}
+
+#[derive(Debug, MiniClap)]
+struct Transformed {
+    #[miniclap(long, transform = "trim")]
+    port: Option<u16>,
+
+    #[miniclap(long, transform = "uppercase")]
+    level: Option<String>,
+
+    #[miniclap(long, transform = "strip_comment", value_delimiters = ",")]
+    tags: Vec<String>,
+}
+
+#[test]
+fn transform_values() {
+    let opts = Transformed::try_parse_from([
+        "test",
+        "--port",
+        " 8080\n",
+        "--level=warn",
+        "--tags",
+        "a,b # old,c",
+    ])
+    .unwrap();
+    assert_eq!(opts.port, Some(8080));
+    assert_eq!(opts.level.as_deref(), Some("WARN"));
+    assert_eq!(opts.tags, ["a", "b", "c"]);
+
+    // The value is transformed before parsing, so errors show it transformed.
+    let err = Transformed::try_parse_from(["test", "--port", " x "]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    assert!(err.message.starts_with("Argument 'port' cannot parse 'x'"));
+}
+
+#[derive(Debug, MiniClap)]
+struct Configured {
+    #[miniclap(long, config_file)]
+    config: Option<std::path::PathBuf>,
+
+    #[miniclap(long)]
+    host: String,
+
+    #[miniclap(long, default_value = 80)]
+    port: u16,
+
+    #[miniclap(long)]
+    verbose: bool,
+
+    #[miniclap(long)]
+    tag: Vec<String>,
+}
+
+thread_local! {
+    static PLUGIN_COLORS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    static PLUGIN_WIDTH: std::cell::Cell<Option<u16>> = const { std::cell::Cell::new(None) };
+}
+
+fn plugins(app: &mut miniclap::AppBuilder) {
+    app.flag(miniclap::Switch::Long("color"), || {
+        PLUGIN_COLORS.with(|x| x.set(x.get() + 1))
+    })
+    .option(miniclap::Switch::Both('w', "width"), |width: u16| {
+        PLUGIN_WIDTH.with(|x| x.set(Some(width)))
+    });
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(augment = "plugins")]
+struct Host {
+    #[miniclap(long, config_file)]
+    config: Option<std::path::PathBuf>,
+
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(long)]
+    name: Option<String>,
+}
+
+#[derive(Debug, MiniClap)]
+#[allow(dead_code)]
+struct Sizes {
+    #[miniclap(long)]
+    width: Option<u16>,
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(augment = "plugins")]
+#[allow(dead_code)]
+struct ClashingHost {
+    #[miniclap(flatten)]
+    sizes: Sizes,
+}
+
+#[test]
+fn augment_adds_arguments() {
+    let path = std::env::temp_dir().join(format!("miniclap-augment-{}.ini", std::process::id()));
+    std::fs::write(&path, "name = example.com\n").unwrap();
+    let config = format!("--config={}", path.display());
+
+    let opts =
+        Host::try_parse_from(["test", "--color", "-v", "-w", "3", &config, "--color"]).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.config.as_deref(), Some(path.as_path()));
+    assert_eq!(opts.name.as_deref(), Some("example.com"));
+    assert_eq!(PLUGIN_COLORS.with(|x| x.get()), 2);
+    assert_eq!(PLUGIN_WIDTH.with(|x| x.get()), Some(3));
+
+    let err = Host::try_parse_from(["test", "--width=x"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+
+    // An added switch may not reuse one of the struct's, including a flattened struct's.
+    let err = ClashingHost::try_parse_from(["test"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::Other));
+    assert_eq!(
+        err.message,
+        "Added argument 'width' uses a switch of argument 'width'"
+    );
+}
+
+#[test]
+fn config_file_defaults() {
+    let path = std::env::temp_dir().join(format!("miniclap-test-{}.ini", std::process::id()));
+    std::fs::write(
+        &path,
+        "# defaults\nhost = example.com\nport = 8080\nverbose = true\ntag = a\n",
+    )
+    .unwrap();
+    let config = format!("--config={}", path.display());
+
+    let opts = Configured::try_parse_from(["test", &config]).unwrap();
+    assert_eq!(opts.config.as_deref(), Some(path.as_path()));
+    assert_eq!(opts.host, "example.com");
+    assert_eq!(opts.port, 8080);
+    assert!(opts.verbose);
+    assert_eq!(opts.tag, ["a"]);
+
+    // The command line overrides the file, wherever the config option is.
+    let opts =
+        Configured::try_parse_from(["test", "--port", "9", "--tag=b", &config, "--tag=c"]).unwrap();
+    assert_eq!(opts.host, "example.com");
+    assert_eq!(opts.port, 9);
+    assert_eq!(opts.tag, ["b", "c"]);
+
+    let opts = Configured::try_parse_from(["test", "--host=x"]).unwrap();
+    assert_eq!((opts.host.as_str(), opts.port), ("x", 80));
+
+    std::fs::remove_file(&path).unwrap();
+    let err = Configured::try_parse_from(["test", &config]).unwrap_err();
+    assert!(err.message.starts_with("Cannot read config file"));
+}
+
+#[derive(Debug, MiniClap)]
+enum ConfiguredCommand {
+    Run {
+        #[miniclap(long)]
+        config: Option<String>,
+    },
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(max_args = 4)]
+struct ConfiguredRun {
+    #[miniclap(long, config_file)]
+    config: Option<std::path::PathBuf>,
+
+    #[miniclap(long)]
+    name: Option<String>,
+
+    #[miniclap(subcommand)]
+    command: ConfiguredCommand,
+}
+
+#[test]
+fn config_file_found_by_parsing() {
+    // Only the option itself names the file, not the value of another option or an option of
+    // the subcommand, so no file is read here.
+    let opts =
+        ConfiguredRun::try_parse_from(["test", "--name", "--config", "run", "--config=x"]).unwrap();
+    assert_eq!(opts.config, None);
+    assert_eq!(opts.name.as_deref(), Some("--config"));
+    let ConfiguredCommand::Run { config } = opts.command;
+    assert_eq!(config.as_deref(), Some("x"));
+
+    // The arguments are still pulled lazily, so parsing stops at the first one over the cap.
+    let mut args = [
+        "test",
+        "--config=a",
+        "--config=b",
+        "--config=c",
+        "--config=d",
+        "x",
+    ]
+    .iter()
+    .map(OsString::from)
+    .chain(std::iter::from_fn(|| panic!("pulled past the cap")));
+    let err = ConfiguredRun::try_parse_from(&mut args).unwrap_err();
+    assert_eq!(err.message, "Too many arguments (expected at most 4)");
+}
+
+#[derive(Debug, MiniClap)]
+struct Remaining {
+    #[miniclap(short, long)]
+    recursive: bool,
+
+    #[miniclap(long)]
+    pattern: Option<String>,
+
+    dest: String,
+
+    #[miniclap(remaining)]
+    sources: Vec<OsString>,
+}
+
+#[test]
+fn remaining_positionals() {
+    let opts = Remaining::try_parse_from([
+        "test",
+        "out",
+        "a.txt",
+        "-r",
+        "b.txt",
+        "--pattern=*",
+        "--",
+        "-c",
+    ])
+    .unwrap();
+    assert!(opts.recursive);
+    assert_eq!(opts.pattern.as_deref(), Some("*"));
+    assert_eq!(opts.dest, "out");
+    assert_eq!(opts.sources, ["a.txt", "b.txt", "-c"]);
+
+    let opts = Remaining::try_parse_from(["test", "out"]).unwrap();
+    assert!(opts.sources.is_empty());
+    assert!(Remaining::try_parse_from(["test", "-r"]).is_err());
+}
+
+#[derive(Debug, PartialEq)]
+enum Color {
+    Auto,
+    Never,
+}
+
+impl From<bool> for Color {
+    fn from(never: bool) -> Color {
+        if never {
+            Color::Never
+        } else {
+            Color::Auto
+        }
+    }
+}
+
+#[derive(Debug, MiniClap)]
+struct CoercedFlags {
+    #[miniclap(long = "no-color", takes_value = false)]
+    color: Color,
+
+    #[miniclap(short, takes_value = false)]
+    quiet: bool,
+}
+
+#[test]
+fn takes_value_false() {
+    let opts = CoercedFlags::try_parse_from(["test", "--no-color", "-q"]).unwrap();
+    assert_eq!(opts.color, Color::Never);
+    assert!(opts.quiet);
+
+    let opts = CoercedFlags::try_parse_from(["test"]).unwrap();
+    assert_eq!(opts.color, Color::Auto);
+    assert!(!opts.quiet);
+
+    let err = CoercedFlags::try_parse_from(["test", "--no-color=never"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::UnexpectedValue));
+}
+
+#[derive(Debug, PartialEq, MiniClap)]
+#[miniclap(to_args)]
+struct LogOpts {
+    #[miniclap(short, count)]
+    verbose: u8,
+
+    #[miniclap(long)]
+    log_file: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, PartialEq, MiniClap)]
+#[miniclap(to_args)]
+struct Fetch {
+    #[miniclap(short, long)]
+    force: bool,
+
+    #[miniclap(long, default_value = 3)]
+    retries: u32,
+
+    #[miniclap(long, number_base = 16)]
+    mask: Option<u32>,
+
+    #[miniclap(long)]
+    timeout: Option<std::time::Duration>,
+
+    #[miniclap(short)]
+    header: Vec<String>,
+
+    #[miniclap(flatten)]
+    logging: LogOpts,
+
+    url: String,
+
+    dest: Option<String>,
+}
+
+#[derive(Debug, PartialEq, MiniClap)]
+#[miniclap(to_args)]
+enum Remote {
+    Add {
+        #[miniclap(long)]
+        fetch: bool,
+        name: String,
+    },
+    Fetch(Fetch),
+}
+
+#[derive(Debug, PartialEq, MiniClap)]
+#[miniclap(to_args)]
+struct Git {
+    #[miniclap(long)]
+    dry_run: Option<bool>,
+
+    #[miniclap(subcommand)]
+    remote: Remote,
+}
+
+#[test]
+fn to_args_round_trips() {
+    let opts = Fetch::try_parse_from([
+        "test",
+        "-vv",
+        "-h",
+        "a: 1",
+        "--mask=ff",
+        "--timeout",
+        "1.5s",
+        "-h",
+        "b: 2",
+        "-f",
+        "url",
+        "-",
+        "--log_file=out.log",
+    ])
+    .unwrap();
+    let args = opts.to_args();
+    assert_eq!(
+        args,
+        [
+            "--force",
+            "--mask",
+            "ff",
+            "--timeout",
+            "1s500ms",
+            "-h",
+            "a: 1",
+            "-h",
+            "b: 2",
+            "-v",
+            "-v",
+            "--log_file",
+            "out.log",
+            "url",
+            "-",
+        ]
+    );
+    let reparsed = Fetch::try_parse_from(std::iter::once("test".to_string()).chain(args));
+    assert_eq!(reparsed.unwrap(), opts);
+
+    // Defaults are left out, and a positional that looks like a switch follows `--`.
+    let opts = Fetch::try_parse_from(["test", "--retries", "3", "--", "-x"]).unwrap();
+    assert_eq!(opts.to_args(), ["--", "-x"]);
+
+    let opts = Git::try_parse_from(["test", "--no-dry_run", "fetch", "--retries=5", "u"]).unwrap();
+    let args = opts.to_args();
+    assert_eq!(args, ["--no-dry_run", "fetch", "--retries", "5", "u"]);
+    let reparsed = Git::try_parse_from(std::iter::once("test".to_string()).chain(args));
+    assert_eq!(reparsed.unwrap(), opts);
+
+    let opts = Git::try_parse_from(["test", "add", "origin", "--fetch"]).unwrap();
+    assert_eq!(opts.to_args(), ["add", "--fetch", "origin"]);
+}
+
+#[derive(Debug, MiniClap)]
+#[miniclap(to_args)]
+struct Defines {
+    #[miniclap(short = 'D', value_delimiters = ",")]
+    defines: Vec<(String, i32)>,
+}
+
+#[test]
+fn key_value_pairs() {
+    let opts = Defines::try_parse_from(["test", "-D", "b=2", "-Da=1", "-D", "a=-3,c=4"]).unwrap();
+    assert_eq!(
+        opts.defines,
+        [
+            ("b".to_string(), 2),
+            ("a".to_string(), 1),
+            ("a".to_string(), -3),
+            ("c".to_string(), 4)
+        ]
+    );
+    assert_eq!(
+        opts.to_args(),
+        ["-D", "b=2", "-D", "a=1", "-D", "a=-3", "-D", "c=4"]
+    );
+
+    let err = Defines::try_parse_from(["test", "-D", "a"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    assert_eq!(
+        err.message,
+        "Argument 'defines' cannot parse 'a': expected a key and value such as 'key=value'"
+    );
+    let err = Defines::try_parse_from(["test", "-D", "a=x"]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Argument 'defines' cannot parse 'x': invalid digit found in string"
+    );
+}
+
+#[derive(Debug, MiniClap)]
+struct Supplied {
+    #[miniclap(long)]
+    host: Option<String>,
+
+    #[miniclap(long, default_value = 80)]
+    port: u16,
+
+    #[miniclap(short)]
+    quiet: bool,
+
+    #[miniclap(long)]
+    tags: Vec<String>,
+
+    #[miniclap(flatten)]
+    logging: LogOpts,
+
+    path: Option<std::path::PathBuf>,
+}
+
+#[test]
+fn supplied_defaults() {
+    let defaults = |name: &str| match name {
+        "host" => Some("example.com".to_string()),
+        "port" => Some("8080".to_string()),
+        "quiet" => Some("true".to_string()),
+        "tags" => Some("a".to_string()),
+        "log_file" => Some("out.log".to_string()),
+        "path" => Some("/tmp".to_string()),
+        _ => None,
+    };
+    let opts = Supplied::try_parse_from_with_defaults(["test"], defaults).unwrap();
+    assert_eq!(opts.host.as_deref(), Some("example.com"));
+    assert_eq!(opts.port, 8080);
+    assert!(opts.quiet);
+    assert_eq!(opts.tags, ["a"]);
+    assert_eq!(opts.logging.log_file, Some("out.log".into()));
+    assert_eq!(opts.path, Some("/tmp".into()));
+
+    // The command line takes precedence.
+    let opts = Supplied::try_parse_from_with_defaults(
+        ["test", "--port=1", "--tags", "b", "--tags", "c", "-vv", "."],
+        defaults,
+    )
+    .unwrap();
+    assert_eq!(opts.port, 1);
+    assert_eq!(opts.tags, ["b", "c"]);
+    assert_eq!(opts.logging.verbose, 2);
+    assert_eq!(opts.path, Some(".".into()));
+
+    let err = Supplied::try_parse_from_with_defaults(["test"], |name| match name {
+        "port" => Some("http".to_string()),
+        _ => None,
+    })
+    .unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+
+    // Defaults only apply to the parse they were given to.
+    let opts = Supplied::try_parse_from(["test"]).unwrap();
+    assert_eq!(opts.port, 80);
+    assert!(!opts.quiet);
+}
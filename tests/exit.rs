@@ -0,0 +1,193 @@
+use miniclap::{Error, ErrorKind, ExitResult, MiniClap};
+use std::process::{Command, ExitCode, Termination};
+
+const CHILD_ENV: &str = "MINICLAP_EXIT_TEST_CHILD";
+
+#[derive(Debug, MiniClap)]
+#[miniclap(validate = "Bounds::check")]
+struct Bounds {
+    #[miniclap(long)]
+    min: u32,
+
+    #[miniclap(long)]
+    max: u32,
+}
+
+impl Bounds {
+    fn check(&self) -> miniclap::Result<()> {
+        if self.min > self.max {
+            return Err(Error::validation("--min must not exceed --max"));
+        }
+        Ok(())
+    }
+}
+
+/// Runs inside the child process spawned by `exit_flushes_output`.
+#[test]
+fn exit_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    // No trailing newline, so this stays in the stdout buffer until flushed.
+    print!("partial output");
+    Error::other("boom").exit();
+}
+
+#[test]
+fn exit_flushes_output() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["exit_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.ends_with("partial output"), "stdout: {:?}", stdout);
+    assert!(stderr.ends_with("error: boom\n"), "stderr: {:?}", stderr);
+}
+
+/// Runs inside the child process spawned by `parse_or_exit_validates`.
+#[test]
+fn validate_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    Bounds::parse_or_exit_from(["test", "--min=5", "--max=1"]);
+}
+
+#[test]
+fn parse_or_exit_validates() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "validate_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.ends_with("error: --min must not exceed --max\n"),
+        "stderr: {:?}",
+        stderr
+    );
+}
+
+/// Runs inside the child process spawned by `usage_error_exits_with_2`.
+#[test]
+fn usage_error_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    Bounds::parse_or_exit_from(["test", "--min=1", "--mix=5"]);
+}
+
+#[test]
+fn usage_error_exits_with_2() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "usage_error_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr.ends_with("error: Did not recognize argument '--mix' (did you mean '--min'?)\n"),
+        "stderr: {:?}",
+        stderr
+    );
+}
+
+/// Runs inside the child process spawned by `custom_error_report`.
+#[test]
+fn custom_report_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    Bounds::parse_or_exit_from_with(["test", "--min=1"], |e| {
+        println!("{{\"error\": {:?}}}", e.message);
+        match e.kind {
+            ErrorKind::MissingRequiredArgument => 3,
+            _ => 4,
+        }
+    });
+}
+
+#[test]
+fn custom_error_report() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "custom_report_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        stdout.ends_with("{\"error\": \"Missing required argument '--max'\"}\n"),
+        "stdout: {:?}",
+        stdout
+    );
+    assert!(!stderr.contains("error:"), "stderr: {:?}", stderr);
+}
+
+#[test]
+fn try_parse_skips_validate() {
+    let opts = Bounds::try_parse_from(["test", "--min=5", "--max=1"]).unwrap();
+    let err = opts.validate().unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Validation));
+
+    let opts = Bounds::parse_or_exit_from(["test", "--min=1", "--max=5"]);
+    assert!(opts.validate().is_ok());
+}
+
+/// Runs inside the child process spawned by `exit_result_reports_errors`.
+#[test]
+fn exit_result_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    let result = Bounds::try_parse_from(["test", "--min=x"]).map(|_| ());
+    Termination::report(ExitResult::from(result));
+}
+
+#[test]
+fn exit_result_reports_errors() {
+    let result = Bounds::try_parse_from(["test", "--min=x"]).map(|_| ());
+    assert_eq!(ExitResult::from(result).report(), ExitCode::from(2));
+    let result = Bounds::try_parse_from(["test", "--min=1", "--max=1"]).map(|_| ());
+    assert_eq!(ExitResult::from(result).report(), ExitCode::SUCCESS);
+    let err = Error::validation("--min must not exceed --max");
+    assert_eq!(ExitResult::from(err).report(), ExitCode::from(1));
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "exit_result_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: Argument 'min' cannot parse 'x': invalid digit found in string\n"),
+        "stderr: {:?}",
+        stderr
+    );
+}
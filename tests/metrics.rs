@@ -0,0 +1,54 @@
+use miniclap::{MiniClap, ParseMetrics};
+
+#[derive(Debug, MiniClap)]
+enum Tool {
+    Run {
+        #[miniclap(short)]
+        quick: bool,
+    },
+}
+
+#[derive(Debug, MiniClap)]
+struct Switches {
+    #[miniclap(short)]
+    a: bool,
+
+    #[miniclap(short)]
+    b: bool,
+
+    #[miniclap(short)]
+    c: bool,
+
+    #[miniclap(long)]
+    level: u8,
+
+    #[miniclap(subcommand)]
+    tool: Option<Tool>,
+}
+
+#[test]
+fn metrics_count_lookups() {
+    let res = Switches::try_parse_from_with_warnings(["test", "-c", "--level=3", "-ab"]).unwrap();
+    assert!(res.value.a && res.value.b && res.value.c);
+    assert_eq!(res.value.level, 3);
+    // Each switch is looked up among both the flags and the options, scanning until a match.
+    assert_eq!(
+        res.metrics,
+        ParseMetrics {
+            lookups: 8,
+            handlers_scanned: 4 + 4 + 2 + 3,
+        }
+    );
+
+    // The lookups of a subcommand's own parse are counted too.
+    let res =
+        Switches::try_parse_from_with_warnings(["test", "-c", "--level=3", "run", "-q"]).unwrap();
+    assert!(matches!(res.value.tool, Some(Tool::Run { quick: true })));
+    assert_eq!(
+        res.metrics,
+        ParseMetrics {
+            lookups: 6,
+            handlers_scanned: 4 + 4 + 1,
+        }
+    );
+}
@@ -0,0 +1,73 @@
+use miniclap::{with_prompt_reader, MiniClap};
+use std::io::Cursor;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, MiniClap)]
+struct Login {
+    #[miniclap(long)]
+    user: String,
+
+    #[miniclap(long, prompt = "Password: ")]
+    password: String,
+
+    #[miniclap(long, prompt = "PIN: ")]
+    pin: Option<u32>,
+}
+
+#[test]
+fn prompts_for_missing_values() {
+    let input = Cursor::new("hunter2\n1234\n");
+    let opts =
+        with_prompt_reader(input, || Login::try_parse_from(["test", "--user", "me"])).unwrap();
+    assert_eq!(opts.user, "me");
+    assert_eq!(opts.password, "hunter2");
+    assert_eq!(opts.pin, Some(1234));
+}
+
+#[test]
+fn given_values_are_not_prompted_for() {
+    let input = Cursor::new("1234\n");
+    let opts = with_prompt_reader(input, || {
+        Login::try_parse_from(["test", "--user=me", "--password", "secret"])
+    })
+    .unwrap();
+    assert_eq!(opts.password, "secret");
+    assert_eq!(opts.pin, Some(1234));
+}
+
+#[test]
+fn prompted_values_are_parsed() {
+    let input = Cursor::new("secret\nabc\n");
+    let err =
+        with_prompt_reader(input, || Login::try_parse_from(["test", "--user=me"])).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::ParseFailed));
+    assert!(err
+        .message
+        .starts_with("Argument '--pin' cannot parse 'abc'"));
+}
+
+const CHILD_ENV: &str = "MINICLAP_PROMPT_TEST_CHILD";
+
+/// Runs inside the child process spawned by `no_terminal_is_missing`, whose stdin is not a
+/// terminal.
+#[test]
+fn no_terminal_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    let err = Login::try_parse_from(["test", "--user=me"]).unwrap_err();
+    assert_eq!(err.message, "Missing required argument '--password'");
+    let opts = Login::try_parse_from(["test", "--user=me", "--password=x"]).unwrap();
+    assert_eq!(opts.pin, None);
+}
+
+#[test]
+fn no_terminal_is_missing() {
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["no_terminal_child", "--exact", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .stdin(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
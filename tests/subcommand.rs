@@ -0,0 +1,208 @@
+use miniclap::MiniClap;
+
+#[derive(Debug, PartialEq, MiniClap)]
+struct TestOpts {
+    #[miniclap(long)]
+    release: bool,
+
+    filter: Option<String>,
+}
+
+#[derive(Debug, PartialEq, MiniClap)]
+enum Command {
+    #[miniclap(alias = "b")]
+    Build {
+        #[miniclap(short, long)]
+        jobs: Option<u32>,
+    },
+    Test(TestOpts),
+    Bench {
+        #[miniclap(flatten)]
+        test: TestOpts,
+
+        #[miniclap(long)]
+        iterations: Option<u32>,
+    },
+    #[miniclap(hidden_alias = "clean")]
+    CleanAll,
+    Run {
+        #[miniclap(long)]
+        release: bool,
+
+        bin: Option<String>,
+
+        #[miniclap(trailing)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, MiniClap)]
+struct Opts {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, MiniClap)]
+struct OptionalOpts {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    #[miniclap(subcommand)]
+    command: Option<Command>,
+}
+
+#[test]
+fn global_flag_and_subcommand() {
+    let opts = Opts::try_parse_from(["cmd", "--verbose", "build", "-j", "4"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.command, Command::Build { jobs: Some(4) });
+
+    let opts = Opts::try_parse_from(["cmd", "test", "--release", "foo"]).unwrap();
+    assert!(!opts.verbose);
+    assert_eq!(
+        opts.command,
+        Command::Test(TestOpts {
+            release: true,
+            filter: Some("foo".into()),
+        })
+    );
+
+    let opts = Opts::try_parse_from(["cmd", "-v", "clean-all"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.command, Command::CleanAll);
+}
+
+#[test]
+fn flattened_variant_fields() {
+    let opts =
+        Opts::try_parse_from(["cmd", "bench", "--iterations=3", "--release", "foo"]).unwrap();
+    assert_eq!(
+        opts.command,
+        Command::Bench {
+            test: TestOpts {
+                release: true,
+                filter: Some("foo".into()),
+            },
+            iterations: Some(3),
+        }
+    );
+}
+
+#[test]
+fn global_flag_after_subcommand_is_not_global() {
+    let err = Opts::try_parse_from(["cmd", "build", "--verbose"]).unwrap_err();
+    assert_eq!(err.message, "Did not recognize argument '--verbose'");
+}
+
+#[test]
+fn missing_or_unknown_subcommand() {
+    let err = Opts::try_parse_from(["cmd", "-v"]).unwrap_err();
+    assert_eq!(err.message, "Missing subcommand");
+
+    let err = Opts::try_parse_from(["cmd", "deploy"]).unwrap_err();
+    assert_eq!(err.message, "Did not recognize subcommand 'deploy'");
+
+    let opts = OptionalOpts::try_parse_from(["cmd", "-v"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.command, None);
+}
+
+#[test]
+fn subcommand_aliases() {
+    let opts = Opts::try_parse_from(["cmd", "b", "-j", "4"]).unwrap();
+    assert_eq!(opts.command, Command::Build { jobs: Some(4) });
+    let opts = Opts::try_parse_from(["cmd", "clean"]).unwrap();
+    assert_eq!(opts.command, Command::CleanAll);
+    let opts = Opts::try_parse_from(["cmd", "clean-all"]).unwrap();
+    assert_eq!(opts.command, Command::CleanAll);
+
+    // Hidden aliases are left out of the description.
+    let infos = Command::SUBCOMMANDS;
+    assert_eq!(infos.len(), 5);
+    assert_eq!(
+        infos[0],
+        miniclap::SubcommandInfo {
+            name: "build",
+            aliases: &["b"],
+        }
+    );
+    assert_eq!(infos[3].name, "clean-all");
+    assert!(infos[3].aliases.is_empty());
+}
+
+#[test]
+fn enum_at_top_level() {
+    let command = Command::try_parse_from(["cmd", "build", "--jobs=2"]).unwrap();
+    assert_eq!(command, Command::Build { jobs: Some(2) });
+}
+
+#[test]
+fn trailing_arguments_after_terminator() {
+    let opts = Opts::try_parse_from([
+        "cmd",
+        "run",
+        "--release",
+        "app",
+        "--",
+        "--prog-flag",
+        "-v",
+        "x",
+    ])
+    .unwrap();
+    assert!(!opts.verbose);
+    assert_eq!(
+        opts.command,
+        Command::Run {
+            release: true,
+            bin: Some("app".into()),
+            args: vec!["--prog-flag".into(), "-v".into(), "x".into()],
+        }
+    );
+
+    // After `--`, even what would fit the positional argument is trailing.
+    let opts = Opts::try_parse_from(["cmd", "run", "--", "app"]).unwrap();
+    assert_eq!(
+        opts.command,
+        Command::Run {
+            release: false,
+            bin: None,
+            args: vec!["app".into()],
+        }
+    );
+
+    let err = Opts::try_parse_from(["cmd", "run", "app", "--prog-flag"]).unwrap_err();
+    assert!(matches!(err.kind, miniclap::ErrorKind::UnknownSwitch));
+}
+
+#[derive(Debug, MiniClap)]
+struct ProjectOpts {
+    #[miniclap(short, long)]
+    verbose: bool,
+
+    project: String,
+
+    #[miniclap(subcommand)]
+    command: Command,
+}
+
+#[test]
+fn positional_before_subcommand() {
+    let opts =
+        ProjectOpts::try_parse_from(["cmd", "-v", "app", "test", "--release", "foo"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.project, "app");
+    assert_eq!(
+        opts.command,
+        Command::Test(TestOpts {
+            release: true,
+            filter: Some("foo".into()),
+        })
+    );
+
+    // The first positional argument is always the top-level one, even if it names a subcommand.
+    let err = ProjectOpts::try_parse_from(["cmd", "test", "foo"]).unwrap_err();
+    assert_eq!(err.message, "Did not recognize subcommand 'foo'");
+}